@@ -35,6 +35,15 @@ fn main() {
             yksom_bin.push("release");
             yksom_bin.push("yksom");
             let mut vm = Command::new(yksom_bin);
+            let stem = p.file_stem().unwrap().to_str().unwrap();
+            // Tests whose names start with `fractions_` check the behaviour of `--fractions`.
+            if stem.starts_with("fractions_") {
+                vm.arg("--fractions");
+            }
+            // Tests whose names start with `ic_stats_` check the output of `--ic-stats`.
+            if stem.starts_with("ic_stats_") {
+                vm.arg("--ic-stats");
+            }
             vm.args(&["--cp", SOM_LIBS_PATH, p.to_str().unwrap()]);
             vec![("VM", vm)]
         })
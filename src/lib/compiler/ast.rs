@@ -8,6 +8,8 @@ pub struct Class {
     pub supername: Option<Lexeme<StorageT>>,
     pub inst_vars: Vec<Lexeme<StorageT>>,
     pub methods: Vec<Method>,
+    pub class_inst_vars: Vec<Lexeme<StorageT>>,
+    pub class_methods: Vec<Method>,
 }
 
 #[derive(Debug)]
@@ -25,7 +27,11 @@ pub enum MethodName {
 
 #[derive(Debug)]
 pub enum MethodBody {
-    Primitive,
+    /// A primitive, optionally with SOM code (its variables and expressions) which is executed
+    /// if the primitive fails.
+    Primitive {
+        fallback: Option<(Vec<Lexeme<StorageT>>, Vec<Expr>)>,
+    },
     Body {
         vars: Vec<Lexeme<StorageT>>,
         exprs: Vec<Expr>,
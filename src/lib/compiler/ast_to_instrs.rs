@@ -1,10 +1,10 @@
 use std::{
+    cell::UnsafeCell,
     cmp::max,
     collections::hash_map::{self, HashMap},
     path::Path,
 };
 
-use abgc::Gc;
 use itertools::Itertools;
 use lrpar::{Lexeme, Lexer};
 
@@ -16,16 +16,55 @@ use crate::{
     },
     vm::{
         objects::{BlockInfo, Class, Method, MethodBody, String_},
-        val::ValKind,
+        val::{Val, ValKind},
         VM,
     },
 };
 
+/// Primitives whose implementation depends on the class they are declared in, as `(class name,
+/// selector, primitive)` triples. A selector listed here can only be declared as a primitive in
+/// the classes listed alongside it: any other class doing so gets a compile-time error rather than
+/// another class's primitive.
+const CLASS_PRIMITIVES: &[(&str, &str, Primitive)] = &[
+    ("Array", "at:", Primitive::At),
+    ("Array", "at:put:", Primitive::AtPut),
+    ("Array", "length", Primitive::Length),
+    ("Array", "new:", Primitive::NewArray),
+    ("Class", "new", Primitive::New),
+    ("Dictionary", "at:", Primitive::DictionaryAt),
+    ("Dictionary", "at:put:", Primitive::DictionaryAtPut),
+    ("Dictionary", "new", Primitive::DictionaryNew),
+    ("Dictionary", "size", Primitive::DictionarySize),
+    ("Double", "asInteger", Primitive::AsInteger),
+    ("Double", "fromString:", Primitive::DoubleFromString),
+    ("Integer", "fromString:", Primitive::IntegerFromString),
+    ("Integer", "printString:", Primitive::PrintStringRadix),
+    ("Method", "numArgs", Primitive::NumArgs),
+    ("ReadStream", "on:", Primitive::ReadStreamOn),
+    ("String", "asInteger", Primitive::StringAsInteger),
+    ("String", "at:", Primitive::StringAt),
+    ("String", "length", Primitive::StringLength),
+    ("Symbol", "numArgs", Primitive::SymbolNumArgs),
+    ("System", "printString:", Primitive::PrintString),
+    ("Vector", "at:", Primitive::VectorAt),
+    ("Vector", "at:put:", Primitive::VectorAtPut),
+    ("Vector", "new", Primitive::VectorNew),
+    ("Vector", "size", Primitive::VectorSize),
+    ("WriteStream", "on:", Primitive::WriteStreamOn),
+];
+
 pub struct Compiler<'a> {
     lexer: &'a dyn Lexer<StorageT>,
     path: &'a Path,
+    /// The name of the class being compiled. A few primitives share a name across different
+    /// classes, so we need to know which class we're in to choose the right primitive.
+    cls_name: &'a str,
     /// The stack of variables at the current point of evaluation.
     vars_stack: Vec<HashMap<&'a str, usize>>,
+    /// For each method or block currently being compiled (outermost first), are any of its
+    /// variables captured by a nested block? Methods and blocks whose variables are not captured
+    /// can store their variables on the stack rather than in a heap-allocated closure.
+    captured: Vec<bool>,
     /// Since SOM's "^" operator returns from the enclosed method, we need to track whether we are
     /// in a closure -- and, if so, how many nested closures we are inside at the current point of
     /// evaluation.
@@ -39,24 +78,38 @@ impl<'a> Compiler<'a> {
         path: &Path,
         astcls: &ast::Class,
     ) -> Result<Class, String> {
-        let mut compiler = Compiler {
-            lexer,
-            path,
-            vars_stack: Vec::new(),
-            closure_depth: 0,
-        };
+        let name = lexer.span_str(astcls.name.span());
+        Compiler::compile_as(vm, lexer, path, name, astcls)
+    }
 
+    /// Compile `astcls` as if it were called `name`, which determines the class-specific
+    /// primitives that its methods can use.
+    pub fn compile_as(
+        vm: &VM,
+        lexer: &dyn Lexer<StorageT>,
+        path: &Path,
+        name: &str,
+        astcls: &ast::Class,
+    ) -> Result<Class, String> {
         let mut errs = vec![];
-        let name = lexer.span_str(astcls.name.span()).to_owned();
+        let name = name.to_owned();
         let supercls;
         if name != "Object" {
-            if let Some(n) = astcls.supername.map(|x| lexer.span_str(x.span())) {
+            if let Some(lexeme) = astcls.supername {
+                let n = lexer.span_str(lexeme.span());
                 supercls = match n {
                     "Block" => Some(vm.block_cls.clone()),
                     "Boolean" => Some(vm.bool_cls.clone()),
+                    "Method" => Some(vm.method_cls.clone()),
                     "nil" => None,
                     "String" => Some(vm.str_cls.clone()),
-                    _ => unimplemented!(),
+                    _ => match vm.load_class(n) {
+                        Some(cls) => Some(cls),
+                        None => {
+                            errs.push((lexeme, format!("Unknown superclass '{}'", n)));
+                            None
+                        }
+                    },
                 };
             } else {
                 supercls = Some(vm.obj_cls.clone());
@@ -71,10 +124,31 @@ impl<'a> Compiler<'a> {
             supercls = None;
         }
 
-        let mut inst_vars = HashMap::with_capacity(astcls.inst_vars.len());
-        for lexeme in &astcls.inst_vars {
-            let vars_len = inst_vars.len();
-            inst_vars.insert(lexer.span_str(lexeme.span()), vars_len);
+        // Instances of this class store the superclass's instance variables first, followed by
+        // this class's own instance variables.
+        let mut all_inst_vars = match &supercls {
+            Some(scls) => scls.downcast::<Class>(vm).unwrap().inst_vars.clone(),
+            None => vec![],
+        };
+        all_inst_vars.extend(
+            astcls
+                .inst_vars
+                .iter()
+                .map(|x| lexer.span_str(x.span()).to_owned()),
+        );
+
+        let mut compiler = Compiler {
+            lexer,
+            path,
+            cls_name: &name,
+            vars_stack: Vec::new(),
+            captured: Vec::new(),
+            closure_depth: 0,
+        };
+
+        let mut inst_vars = HashMap::with_capacity(all_inst_vars.len());
+        for (i, n) in all_inst_vars.iter().enumerate() {
+            inst_vars.insert(n.as_str(), i);
         }
         compiler.vars_stack.push(inst_vars);
 
@@ -82,13 +156,36 @@ impl<'a> Compiler<'a> {
         for astmeth in &astcls.methods {
             match compiler.c_method(vm, &astmeth) {
                 Ok(m) => {
-                    methods.insert(m.name.clone(), Gc::new(m));
+                    methods.insert(vm.intern_selector(&m.name), Val::from_obj(vm, m));
+                }
+                Err(mut e) => {
+                    errs.extend(e.drain(..));
+                }
+            }
+        }
+        compiler.vars_stack.pop();
+
+        // Class methods are executed with the class itself as `self`. Since classes aren't
+        // instances of `Inst`, there are no instance variables visible to class methods.
+        if let Some(lexeme) = astcls.class_inst_vars.first() {
+            errs.push((
+                *lexeme,
+                "Class-side instance variables are not supported".to_owned(),
+            ));
+        }
+        compiler.vars_stack.push(HashMap::new());
+        let mut class_methods = HashMap::with_capacity(astcls.class_methods.len());
+        for astmeth in &astcls.class_methods {
+            match compiler.c_method(vm, &astmeth) {
+                Ok(m) => {
+                    class_methods.insert(vm.intern_selector(&m.name), Val::from_obj(vm, m));
                 }
                 Err(mut e) => {
                     errs.extend(e.drain(..));
                 }
             }
         }
+        compiler.vars_stack.pop();
 
         if !errs.is_empty() {
             let err_strs = errs
@@ -109,12 +206,42 @@ impl<'a> Compiler<'a> {
             return Err(err_strs);
         }
 
+        // A class only needs its own metaclass if it has class methods, or if it needs to inherit
+        // class methods from its superclass: all other classes are direct instances of `Class`.
+        // Note that when `Object` and `Class` themselves are compiled, `vm.cls_cls` is not yet
+        // initialised, so neither can have class methods.
+        let meta_supercls = match &supercls {
+            Some(scls) => scls.get_class(vm),
+            None => vm.cls_cls.clone(),
+        };
+        let metacls = if class_methods.is_empty() && meta_supercls.bit_eq(&vm.cls_cls) {
+            None
+        } else {
+            let metacls = Val::from_obj(
+                vm,
+                Class {
+                    name: String_::new(vm, format!("{} class", name)),
+                    path: compiler.path.to_path_buf(),
+                    supercls: Some(meta_supercls),
+                    metacls: None,
+                    inst_vars: vec![],
+                    methods: UnsafeCell::new(class_methods),
+                },
+            );
+            metacls
+                .downcast::<Class>(vm)
+                .unwrap()
+                .bind_methods(vm, &metacls);
+            Some(metacls)
+        };
+
         Ok(Class {
-            name: String_::new(vm, name, true),
-            path: compiler.path.to_path_buf(),
+            name: String_::new(vm, name),
+            path: path.to_path_buf(),
             supercls,
-            num_inst_vars: astcls.inst_vars.len(),
-            methods,
+            metacls,
+            inst_vars: all_inst_vars,
+            methods: UnsafeCell::new(methods),
         })
     }
 
@@ -144,7 +271,7 @@ impl<'a> Compiler<'a> {
             }
         };
         let body = self.c_body(vm, (name.0, &name.1), args, &astmeth.body)?;
-        Ok(Method { name: name.1, body })
+        Ok(Method::new(name.1, body))
     }
 
     fn c_body(
@@ -168,125 +295,242 @@ impl<'a> Compiler<'a> {
         };
 
         match body {
-            ast::MethodBody::Primitive => match name.1 {
-                "+" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::Add))
-                }
-                "-" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::Sub))
-                }
-                "*" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::Mul))
-                }
-                "/" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::Div))
-                }
-                "//" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::DoubleDiv))
-                }
-                "%" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::Mod))
-                }
-                "=" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::Equals))
-                }
-                "==" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::RefEquals))
-                }
-                "~=" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::NotEquals))
-                }
-                "<<" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::Shl))
-                }
-                "<" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::LessThan))
-                }
-                "<=" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::LessThanEquals))
-                }
-                ">" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::GreaterThan))
-                }
-                ">=" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::GreaterThanEquals))
-                }
-                "&" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::And))
-                }
-                "bitXor:" => {
-                    requires_args(1)?;
-                    Ok(MethodBody::Primitive(Primitive::BitXor))
-                }
-                "sqrt" => Ok(MethodBody::Primitive(Primitive::Sqrt)),
-                "asString" => Ok(MethodBody::Primitive(Primitive::AsString)),
-                "asSymbol" => Ok(MethodBody::Primitive(Primitive::AsSymbol)),
-                "class" => Ok(MethodBody::Primitive(Primitive::Class)),
-                "concatenate:" => Ok(MethodBody::Primitive(Primitive::Concatenate)),
-                "global:" => Ok(MethodBody::Primitive(Primitive::Global)),
-                "global:put:" => Ok(MethodBody::Primitive(Primitive::GlobalPut)),
-                "halt" => Ok(MethodBody::Primitive(Primitive::Halt)),
-                "hashcode" => Ok(MethodBody::Primitive(Primitive::Hashcode)),
-                "inspect" => Ok(MethodBody::Primitive(Primitive::Inspect)),
-                "instVarAt:" => Ok(MethodBody::Primitive(Primitive::InstVarAt)),
-                "instVarAt:put:" => Ok(MethodBody::Primitive(Primitive::InstVarAtPut)),
-                "instVarNamed:" => Ok(MethodBody::Primitive(Primitive::InstVarNamed)),
-                "name" => Ok(MethodBody::Primitive(Primitive::Name)),
-                "new" => Ok(MethodBody::Primitive(Primitive::New)),
-                "objectSize" => Ok(MethodBody::Primitive(Primitive::ObjectSize)),
-                "perform:" => Ok(MethodBody::Primitive(Primitive::Perform)),
-                "perform:inSuperclass:" => {
-                    Ok(MethodBody::Primitive(Primitive::PerformInSuperClass))
-                }
-                "perform:withArguments:" => {
-                    Ok(MethodBody::Primitive(Primitive::PerformWithArguments))
+            ast::MethodBody::Primitive { fallback } => {
+                let body = if CLASS_PRIMITIVES.iter().any(|(_, sel, _)| *sel == name.1) {
+                    match CLASS_PRIMITIVES
+                        .iter()
+                        .find(|(cls, sel, _)| *cls == self.cls_name && *sel == name.1)
+                    {
+                        Some((_, _, prim)) => Ok(MethodBody::Primitive(*prim)),
+                        None => Err(vec![(
+                            name.0,
+                            format!(
+                                "Primitive '{}' is not available in class '{}'",
+                                name.1, self.cls_name
+                            ),
+                        )]),
+                    }
+                } else {
+                    match name.1 {
+                        "+" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::Add))
+                        }
+                        "-" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::Sub))
+                        }
+                        "*" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::Mul))
+                        }
+                        "/" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::Div))
+                        }
+                        "//" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::DoubleDiv))
+                        }
+                        "%" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::Mod))
+                        }
+                        "=" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::Equals))
+                        }
+                        "==" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::RefEquals))
+                        }
+                        "~=" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::NotEquals))
+                        }
+                        "<<" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::Shl))
+                        }
+                        "<" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::LessThan))
+                        }
+                        "<=" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::LessThanEquals))
+                        }
+                        ">" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::GreaterThan))
+                        }
+                        ">=" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::GreaterThanEquals))
+                        }
+                        "&" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::And))
+                        }
+                        ">>>" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::Shr))
+                        }
+                        "bitAnd:" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::And))
+                        }
+                        "bitOr:" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::Or))
+                        }
+                        "bitXor:" => {
+                            requires_args(1)?;
+                            Ok(MethodBody::Primitive(Primitive::BitXor))
+                        }
+                        "sqrt" => Ok(MethodBody::Primitive(Primitive::Sqrt)),
+                        "abs" => Ok(MethodBody::Primitive(Primitive::Abs)),
+                        "addMethod:source:" => Ok(MethodBody::Primitive(Primitive::AddMethod)),
+                        "append:" => Ok(MethodBody::Primitive(Primitive::Append)),
+                        "asArray" => Ok(MethodBody::Primitive(Primitive::AsArray)),
+                        "asDouble" => Ok(MethodBody::Primitive(Primitive::AsDouble)),
+                        "asLowercase" => Ok(MethodBody::Primitive(Primitive::AsLowercase)),
+                        "asString" => Ok(MethodBody::Primitive(Primitive::AsString)),
+                        "asSymbol" => Ok(MethodBody::Primitive(Primitive::AsSymbol)),
+                        "asUppercase" => Ok(MethodBody::Primitive(Primitive::AsUppercase)),
+                        "atEnd" => Ok(MethodBody::Primitive(Primitive::AtEnd)),
+                        "beginsWith:" => Ok(MethodBody::Primitive(Primitive::BeginsWith)),
+                        "ceiling" => Ok(MethodBody::Primitive(Primitive::Ceiling)),
+                        "class" => Ok(MethodBody::Primitive(Primitive::Class)),
+                        "concatenate:" => Ok(MethodBody::Primitive(Primitive::Concatenate)),
+                        "contents" => Ok(MethodBody::Primitive(Primitive::Contents)),
+                        "containsKey:" => Ok(MethodBody::Primitive(Primitive::ContainsKey)),
+                        "cos" => Ok(MethodBody::Primitive(Primitive::Cos)),
+                        "denominator" => Ok(MethodBody::Primitive(Primitive::Denominator)),
+                        "endsWith:" => Ok(MethodBody::Primitive(Primitive::EndsWith)),
+                        "errorPrint:" => Ok(MethodBody::Primitive(Primitive::ErrorPrint)),
+                        "errorPrintln:" => Ok(MethodBody::Primitive(Primitive::ErrorPrintln)),
+                        "exit:" => Ok(MethodBody::Primitive(Primitive::Exit)),
+                        "exp" => Ok(MethodBody::Primitive(Primitive::Exp)),
+                        "fields" => Ok(MethodBody::Primitive(Primitive::Fields)),
+                        "floor" => Ok(MethodBody::Primitive(Primitive::Floor)),
+                        "fullGC" => Ok(MethodBody::Primitive(Primitive::FullGC)),
+                        "global:" => Ok(MethodBody::Primitive(Primitive::Global)),
+                        "global:put:" => Ok(MethodBody::Primitive(Primitive::GlobalPut)),
+                        "halt" => Ok(MethodBody::Primitive(Primitive::Halt)),
+                        "hashcode" => Ok(MethodBody::Primitive(Primitive::Hashcode)),
+                        "holder" => Ok(MethodBody::Primitive(Primitive::Holder)),
+                        "indexOf:" => Ok(MethodBody::Primitive(Primitive::IndexOf)),
+                        "inspect" => Ok(MethodBody::Primitive(Primitive::Inspect)),
+                        "instVarAt:" => Ok(MethodBody::Primitive(Primitive::InstVarAt)),
+                        "instVarAt:put:" => Ok(MethodBody::Primitive(Primitive::InstVarAtPut)),
+                        "instVarNamed:" => Ok(MethodBody::Primitive(Primitive::InstVarNamed)),
+                        "invokeOn:with:" => Ok(MethodBody::Primitive(Primitive::InvokeOnWith)),
+                        "isDigits" => Ok(MethodBody::Primitive(Primitive::IsDigits)),
+                        "isInfinite" => Ok(MethodBody::Primitive(Primitive::IsInfinite)),
+                        "isNaN" => Ok(MethodBody::Primitive(Primitive::IsNaN)),
+                        "isLetters" => Ok(MethodBody::Primitive(Primitive::IsLetters)),
+                        "isWhiteSpace" => Ok(MethodBody::Primitive(Primitive::IsWhiteSpace)),
+                        "keys" => Ok(MethodBody::Primitive(Primitive::Keys)),
+                        "load:" => Ok(MethodBody::Primitive(Primitive::Load)),
+                        "log" => Ok(MethodBody::Primitive(Primitive::Log)),
+                        "methods" => Ok(MethodBody::Primitive(Primitive::Methods)),
+                        "name" => Ok(MethodBody::Primitive(Primitive::Name)),
+                        "negated" => Ok(MethodBody::Primitive(Primitive::Negated)),
+                        "next" => Ok(MethodBody::Primitive(Primitive::Next)),
+                        "nextPut:" => Ok(MethodBody::Primitive(Primitive::NextPut)),
+                        "nextPutAll:" => Ok(MethodBody::Primitive(Primitive::NextPutAll)),
+                        "numerator" => Ok(MethodBody::Primitive(Primitive::Numerator)),
+                        "numerator:denominator:" => {
+                            Ok(MethodBody::Primitive(Primitive::FractionNew))
+                        }
+                        "objectSize" => Ok(MethodBody::Primitive(Primitive::ObjectSize)),
+                        "peek" => Ok(MethodBody::Primitive(Primitive::Peek)),
+                        "perform:" => Ok(MethodBody::Primitive(Primitive::Perform)),
+                        "perform:inSuperclass:" => {
+                            Ok(MethodBody::Primitive(Primitive::PerformInSuperClass))
+                        }
+                        "perform:withArguments:" => {
+                            Ok(MethodBody::Primitive(Primitive::PerformWithArguments))
+                        }
+                        "perform:withArguments:inSuperclass:" => Ok(MethodBody::Primitive(
+                            Primitive::PerformWithArgumentsInSuperClass,
+                        )),
+                        "PositiveInfinity" => {
+                            Ok(MethodBody::Primitive(Primitive::PositiveInfinity))
+                        }
+                        "primSubstringFrom:to:" => Ok(MethodBody::Primitive(Primitive::Substring)),
+                        "printNewline" => Ok(MethodBody::Primitive(Primitive::PrintNewline)),
+                        "raisedTo:" => Ok(MethodBody::Primitive(Primitive::RaisedTo)),
+                        "rem:" => Ok(MethodBody::Primitive(Primitive::Rem)),
+                        "removeFirst" => Ok(MethodBody::Primitive(Primitive::RemoveFirst)),
+                        "removeLast" => Ok(MethodBody::Primitive(Primitive::RemoveLast)),
+                        "removeKey:" => Ok(MethodBody::Primitive(Primitive::RemoveKey)),
+                        "removeSelector:" => Ok(MethodBody::Primitive(Primitive::RemoveSelector)),
+                        "respondsTo:" => Ok(MethodBody::Primitive(Primitive::RespondsTo)),
+                        "restart" => Ok(MethodBody::Primitive(Primitive::Restart)),
+                        "round" => Ok(MethodBody::Primitive(Primitive::Round)),
+                        "selectors" => Ok(MethodBody::Primitive(Primitive::Selectors)),
+                        "signature" => Ok(MethodBody::Primitive(Primitive::Signature)),
+                        "sin" => Ok(MethodBody::Primitive(Primitive::Sin)),
+                        "sort:" => Ok(MethodBody::Primitive(Primitive::Sort)),
+                        "split:" => Ok(MethodBody::Primitive(Primitive::Split)),
+                        "subclass:instanceFields:" => {
+                            Ok(MethodBody::Primitive(Primitive::Subclass))
+                        }
+                        "superclass" => Ok(MethodBody::Primitive(Primitive::Superclass)),
+                        "ticks" => Ok(MethodBody::Primitive(Primitive::Ticks)),
+                        "time" => Ok(MethodBody::Primitive(Primitive::Time)),
+                        "values" => Ok(MethodBody::Primitive(Primitive::Values)),
+                        "upTo:" => Ok(MethodBody::Primitive(Primitive::UpTo)),
+                        "value" => Ok(MethodBody::Primitive(Primitive::Value(0))),
+                        "value:" => Ok(MethodBody::Primitive(Primitive::Value(1))),
+                        "value:with:" => Ok(MethodBody::Primitive(Primitive::Value(2))),
+                        _ => Err(vec![(name.0, format!("Unknown primitive '{}'", name.1))]),
+                    }
+                }?;
+                match (body, fallback) {
+                    (MethodBody::Primitive(Primitive::Restart), Some(_)) => Err(vec![(
+                        name.0,
+                        "Primitive 'restart' cannot have fallback code".to_owned(),
+                    )]),
+                    (MethodBody::Primitive(prim), Some((vars_lexemes, exprs))) => {
+                        let bytecode_off = vm.instrs_len();
+                        let (num_vars, max_stack, needs_closure) =
+                            self.c_block(vm, true, &params, vars_lexemes, exprs)?;
+                        Ok(MethodBody::PrimitiveWithFallback {
+                            prim,
+                            num_vars,
+                            bytecode_off,
+                            max_stack,
+                            needs_closure,
+                        })
+                    }
+                    (body, _) => Ok(body),
                 }
-                "perform:withArguments:inSuperclass:" => Ok(MethodBody::Primitive(
-                    Primitive::PerformWithArgumentsInSuperClass,
-                )),
-                "printNewline" => Ok(MethodBody::Primitive(Primitive::PrintNewline)),
-                "printString:" => Ok(MethodBody::Primitive(Primitive::PrintString)),
-                "restart" => Ok(MethodBody::Primitive(Primitive::Restart)),
-                "superclass" => Ok(MethodBody::Primitive(Primitive::Superclass)),
-                "value" => Ok(MethodBody::Primitive(Primitive::Value(0))),
-                "value:" => Ok(MethodBody::Primitive(Primitive::Value(1))),
-                "value:with:" => Ok(MethodBody::Primitive(Primitive::Value(2))),
-                _ => Err(vec![(name.0, format!("Unknown primitive '{}'", name.1))]),
-            },
+            }
             ast::MethodBody::Body {
                 vars: vars_lexemes,
                 exprs,
             } => {
                 let bytecode_off = vm.instrs_len();
-                let (num_vars, max_stack) = self.c_block(vm, true, &params, vars_lexemes, exprs)?;
+                let (num_vars, max_stack, needs_closure) =
+                    self.c_block(vm, true, &params, vars_lexemes, exprs)?;
                 Ok(MethodBody::User {
                     num_vars,
                     bytecode_off,
                     max_stack,
+                    needs_closure,
                 })
             }
         }
     }
 
-    /// Evaluate an expression, returning `Ok(max_stack_size)` if successful. Note that there is an
-    /// implicit assumption that primitives never need more stack size than they take in (e.g. if
-    /// they push an item on to the stack, they must have popped at least one element off it
-    /// beforehand).
+    /// Evaluate a method or block body, returning `Ok((num_vars, max_stack_size, needs_closure))`
+    /// if successful, where `needs_closure` is true if any of the body's variables are captured by
+    /// a nested block. Note that there is an implicit assumption that primitives never need more
+    /// stack size than they take in (e.g. if they push an item on to the stack, they must have
+    /// popped at least one element off it beforehand).
     fn c_block(
         &mut self,
         vm: &VM,
@@ -294,7 +538,7 @@ impl<'a> Compiler<'a> {
         params: &[Lexeme<StorageT>],
         vars_lexemes: &[Lexeme<StorageT>],
         exprs: &[ast::Expr],
-    ) -> Result<(usize, usize), Vec<(Lexeme<StorageT>, String)>> {
+    ) -> Result<(usize, usize, bool), Vec<(Lexeme<StorageT>, String)>> {
         let mut vars = HashMap::new();
         if is_method {
             // The VM assumes that the first variable of a method is "self".
@@ -328,6 +572,7 @@ impl<'a> Compiler<'a> {
 
         let num_vars = vars.len();
         self.vars_stack.push(vars);
+        self.captured.push(false);
         let mut max_stack = 0;
         for (i, e) in exprs.iter().enumerate() {
             // We deliberately bomb out at the first error in a method on the basis that
@@ -347,8 +592,9 @@ impl<'a> Compiler<'a> {
         }
         vm.instrs_push(Instr::Return);
         self.vars_stack.pop();
+        let needs_closure = self.captured.pop().unwrap();
 
-        Ok((num_vars, max_stack))
+        Ok((num_vars, max_stack, needs_closure))
     }
 
     /// Evaluate an expression, returning `Ok(max_stack_size)` if successful.
@@ -364,6 +610,7 @@ impl<'a> Compiler<'a> {
                 if depth == self.vars_stack.len() - 1 {
                     vm.instrs_push(Instr::InstVarSet(var_num));
                 } else {
+                    self.capture(depth);
                     vm.instrs_push(Instr::VarSet(depth, var_num));
                 }
                 debug_assert!(max_stack > 0);
@@ -372,7 +619,8 @@ impl<'a> Compiler<'a> {
             ast::Expr::BinaryMsg { lhs, op, rhs } => {
                 let mut stack_size = self.c_expr(vm, lhs)?;
                 stack_size = max(stack_size, 1 + self.c_expr(vm, rhs)?);
-                let send_off = vm.add_send((self.lexer.span_str(op.span()).to_string(), 1));
+                let sel = vm.intern_selector(self.lexer.span_str(op.span()));
+                let send_off = vm.add_send((sel, 1));
                 vm.instrs_push(Instr::Send(send_off, vm.new_inline_cache()));
                 debug_assert!(stack_size > 0);
                 Ok(stack_size)
@@ -382,17 +630,27 @@ impl<'a> Compiler<'a> {
                 vars,
                 exprs,
             } => {
+                // Blocks are instances of `Block`, `Block2`, or `Block3`, so there is no class for
+                // a block with more than 2 parameters.
+                if params.len() > 2 {
+                    return Err(vec![(
+                        params[2],
+                        "Blocks can have at most 2 parameters".to_owned(),
+                    )]);
+                }
                 let blkinfo_idx = vm.push_blockinfo(BlockInfo {
                     bytecode_off: vm.instrs_len(),
                     bytecode_end: 0,
                     num_params: params.len(),
                     num_vars: 0,
                     max_stack: 0,
+                    needs_closure: false,
                 });
                 vm.instrs_push(Instr::Block(blkinfo_idx));
                 self.closure_depth += 1;
                 let bytecode_off = vm.instrs_len();
-                let (num_vars, max_stack) = self.c_block(vm, false, params, vars, exprs)?;
+                let (num_vars, max_stack, needs_closure) =
+                    self.c_block(vm, false, params, vars, exprs)?;
                 self.closure_depth -= 1;
                 vm.set_blockinfo(
                     blkinfo_idx,
@@ -402,6 +660,7 @@ impl<'a> Compiler<'a> {
                         num_params: params.len(),
                         num_vars,
                         max_stack,
+                        needs_closure,
                     },
                 );
                 Ok(1)
@@ -441,7 +700,7 @@ impl<'a> Compiler<'a> {
                     let expr_stack = self.c_expr(vm, expr)?;
                     max_stack = max(max_stack, 1 + i + expr_stack);
                 }
-                let send_off = vm.add_send((mn, msglist.len()));
+                let send_off = vm.add_send((vm.intern_selector(&mn), msglist.len()));
                 vm.instrs_push(Instr::Send(send_off, vm.new_inline_cache()));
                 debug_assert!(max_stack > 0);
                 Ok(max_stack)
@@ -449,7 +708,8 @@ impl<'a> Compiler<'a> {
             ast::Expr::UnaryMsg { receiver, ids } => {
                 let max_stack = self.c_expr(vm, receiver)?;
                 for id in ids {
-                    let send_off = vm.add_send((self.lexer.span_str(id.span()).to_string(), 0));
+                    let sel = vm.intern_selector(self.lexer.span_str(id.span()));
+                    let send_off = vm.add_send((sel, 0));
                     vm.instrs_push(Instr::Send(send_off, vm.new_inline_cache()));
                 }
                 debug_assert!(max_stack > 0);
@@ -460,6 +720,9 @@ impl<'a> Compiler<'a> {
                 if self.closure_depth == 0 {
                     vm.instrs_push(Instr::Return);
                 } else {
+                    // A non-local return identifies the method it returns from by that method's
+                    // closure.
+                    self.capture(self.closure_depth);
                     vm.instrs_push(Instr::ClosureReturn(self.closure_depth));
                 }
                 debug_assert!(max_stack > 0);
@@ -485,6 +748,7 @@ impl<'a> Compiler<'a> {
                         if depth == self.vars_stack.len() - 1 {
                             vm.instrs_push(Instr::InstVarLookup(var_num));
                         } else {
+                            self.capture(depth);
                             vm.instrs_push(Instr::VarLookup(depth, var_num));
                         }
                     }
@@ -495,9 +759,7 @@ impl<'a> Compiler<'a> {
                             "false" => vm.instrs_push(Instr::Builtin(Builtin::False)),
                             "true" => vm.instrs_push(Instr::Builtin(Builtin::True)),
                             _ => {
-                                vm.instrs_push(Instr::Global(
-                                    vm.add_symbol(lex_string.to_string()),
-                                ));
+                                vm.instrs_push(Instr::Global(vm.add_global(lex_string)));
                             }
                         }
                     }
@@ -507,6 +769,16 @@ impl<'a> Compiler<'a> {
         }
     }
 
+    /// Record that the current block accesses a variable `depth` closures away. The closure at
+    /// `depth`, and every closure between it and the current block, must then be heap allocated
+    /// so that the VM can walk from the current block's closure to the variable.
+    fn capture(&mut self, depth: usize) {
+        let len = self.captured.len();
+        for c in &mut self.captured[len - 1 - depth..len - 1] {
+            *c = true;
+        }
+    }
+
     /// Find the variable `name` in the variable stack returning a tuple `Some((depth, var_num))`
     /// or `Err` if the variable isn't found. `depth` is the number of closures away from the
     /// "current" one that the variable is found.
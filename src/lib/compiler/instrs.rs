@@ -9,6 +9,11 @@ pub enum Instr {
     InstVarSet(usize),
     Int(isize),
     Pop,
+    /// A quickened send. The interpreter rewrites a `Send` into one of these at run time if it
+    /// sends a binary message to a tagged integer with a tagged integer argument, and the method
+    /// found is the corresponding `Integer` primitive. It keeps the operands of the `Send` and, if
+    /// its receiver or argument is not a tagged integer, is rewritten back into a `Send`.
+    QuickSend(QuickOp, usize, usize),
     Return,
     Send(usize, usize),
     String(usize),
@@ -25,48 +30,148 @@ pub enum Builtin {
     True,
 }
 
+/// The operations performed by `Instr::QuickSend`.
+#[derive(Clone, Copy, Debug)]
+pub enum QuickOp {
+    AddInt,
+    EqInt,
+    GtInt,
+    GteInt,
+    LtInt,
+    LteInt,
+    MulInt,
+    NeInt,
+    SubInt,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum Primitive {
+    Abs,
     Add,
+    AddMethod,
     And,
+    Append,
+    AsArray,
+    AsDouble,
+    AsInteger,
+    AsLowercase,
     AsString,
     AsSymbol,
+    AsUppercase,
+    At,
+    AtEnd,
+    AtPut,
+    BeginsWith,
     BitXor,
+    Ceiling,
     Class,
     Concatenate,
+    Contents,
+    ContainsKey,
+    Cos,
+    Denominator,
+    DictionaryAt,
+    DictionaryAtPut,
+    DictionaryNew,
+    DictionarySize,
     Div,
     DoubleDiv,
+    DoubleFromString,
+    EndsWith,
     Equals,
+    ErrorPrint,
+    ErrorPrintln,
+    Exit,
+    Exp,
+    Fields,
+    Floor,
+    FractionNew,
+    FullGC,
     Global,
     GlobalPut,
     GreaterThan,
     GreaterThanEquals,
     Halt,
     Hashcode,
+    Holder,
+    IndexOf,
     Inspect,
     InstVarAt,
     InstVarAtPut,
     InstVarNamed,
+    IntegerFromString,
+    InvokeOnWith,
+    IsDigits,
+    IsInfinite,
+    IsNaN,
+    IsLetters,
+    IsWhiteSpace,
+    Keys,
+    Length,
     LessThan,
     LessThanEquals,
+    Load,
+    Log,
+    Methods,
     Mod,
     Mul,
     Name,
+    Negated,
+    Next,
+    NextPut,
+    NextPutAll,
     NotEquals,
     New,
+    NewArray,
+    NumArgs,
+    Numerator,
     ObjectSize,
+    Or,
+    Peek,
     Perform,
     PerformInSuperClass,
     PerformWithArguments,
     PerformWithArgumentsInSuperClass,
+    PositiveInfinity,
     PrintNewline,
     PrintString,
+    PrintStringRadix,
+    RaisedTo,
+    ReadStreamOn,
     RefEquals,
+    Rem,
+    RemoveKey,
+    RemoveFirst,
+    RemoveLast,
+    RemoveSelector,
+    RespondsTo,
     Restart,
+    Round,
+    Selectors,
     Shl,
+    Shr,
+    Signature,
+    Sin,
+    Sort,
+    Split,
     Sqrt,
+    StringAsInteger,
+    StringAt,
+    StringLength,
     Sub,
+    Subclass,
+    Substring,
     Superclass,
+    SymbolNumArgs,
+    Ticks,
+    Time,
+    UpTo,
+    Values,
+    VectorAt,
+    VectorAtPut,
+    VectorNew,
+    VectorSize,
+    WriteStreamOn,
     /// Is this `value` (0), `value:` (1), or `value:with:` (2)?
     Value(u8),
 }
@@ -5,10 +5,15 @@
 
 use std::{fs, path::Path, process};
 
+use itertools::Itertools;
 use lrlex::lrlex_mod;
 use lrpar::lrpar_mod;
 
-use crate::vm::{objects::Class, VM};
+use crate::vm::{
+    objects::{Class, String_},
+    val::Val,
+    VM,
+};
 
 mod ast;
 mod ast_to_instrs;
@@ -22,7 +27,20 @@ type StorageT = u32;
 /// Compile a class. Should only be called by the `VM`.
 pub fn compile(vm: &VM, path: &Path) -> Class {
     let bytes = fs::read(path).unwrap_or_else(|_| panic!("Can't read {}.", path.to_str().unwrap()));
-    let txt = String::from_utf8_lossy(&bytes);
+    let txt = match String::from_utf8(bytes) {
+        Ok(txt) => txt,
+        Err(e) => {
+            let off = e.utf8_error().valid_up_to();
+            let line = e.as_bytes()[..off].iter().filter(|b| **b == b'\n').count() + 1;
+            eprintln!(
+                "{}: invalid UTF-8 at line {} (byte offset {})",
+                path.to_str().unwrap(),
+                line,
+                off
+            );
+            process::exit(1);
+        }
+    };
 
     let lexerdef = som_l::lexerdef();
     let lexer = lexerdef.lexer(&txt);
@@ -50,3 +68,49 @@ pub fn compile(vm: &VM, path: &Path) -> Class {
         }
     }
 }
+
+/// Compile the method whose source is `src` so that it can be added to the class `cls` at run
+/// time, returning a `Method`. Should only be called by the `VM`.
+pub fn compile_method(vm: &VM, cls: &Class, src: &str) -> Result<Val, String> {
+    // The grammar can only parse whole classes, so we wrap `src` in a dummy class with the same
+    // instance variables as `cls`.
+    let txt = if cls.inst_vars.is_empty() {
+        format!("method_source = (\n{}\n)", src)
+    } else {
+        format!(
+            "method_source = ( | {} |\n{}\n)",
+            cls.inst_vars.join(" "),
+            src
+        )
+    };
+
+    let lexerdef = som_l::lexerdef();
+    let lexer = lexerdef.lexer(&txt);
+    let (astopt, errs) = som_y::parse(&lexer);
+    if !errs.is_empty() {
+        return Err(errs
+            .iter()
+            .map(|e| e.pp(&lexer, &som_y::token_epp))
+            .join("\n"));
+    }
+    match astopt {
+        Some(Ok(astcls)) => {
+            if astcls.methods.len() != 1
+                || !astcls.class_inst_vars.is_empty()
+                || !astcls.class_methods.is_empty()
+            {
+                return Err("Expected exactly one method".to_owned());
+            }
+            // Class-specific primitives are chosen by the name of the class being compiled, so we
+            // compile the method as if it were in `cls` (or, if `cls` is a metaclass, the class it
+            // describes, since class methods are compiled alongside instance methods).
+            let cls_name = String_::from_val(vm, &cls.name).unwrap().as_str();
+            let cls_name = cls_name.strip_suffix(" class").unwrap_or(cls_name);
+            let tmpcls =
+                ast_to_instrs::Compiler::compile_as(&vm, &lexer, &cls.path, cls_name, &astcls)?;
+            let meth = tmpcls.methods.into_inner().into_iter().next().unwrap().1;
+            Ok(meth)
+        }
+        _ => Err("Unable to compile method".to_owned()),
+    }
+}
@@ -2,8 +2,13 @@
 %avoid_insert "DOUBLE" "INT" "STRING" "KEYWORD" "ID"
 %%
 ClassDef -> Result<Class, ()>:
-      "ID" "=" SuperClass "(" NameDefs MethodsOpt ClassMethods ")"
-      { Ok(Class{ name: map_err($1)?, supername: $3?, inst_vars: $5?, methods: $6? }) }
+      "ID" "=" SuperClass "(" ClassBody ClassMethods ")"
+      {
+          let (inst_vars, methods) = $5?;
+          let (class_inst_vars, class_methods) = $6?;
+          Ok(Class{ name: map_err($1)?, supername: $3?, inst_vars, methods,
+                    class_inst_vars, class_methods })
+      }
     ;
 SuperClass -> Result<Option<Lexeme<StorageT>>, ()>:
       "ID" { Ok(Some(map_err($1)?)) }
@@ -17,13 +22,24 @@ Methods -> Result<Vec<Method>, ()>:
       Method { Ok(vec![$1?]) }
     | Methods Method { flattenr($1, $2) }
     ;
-ClassMethods -> Result<(), ()>:
-          "SEPARATOR" NameDefs MethodsOpt { unimplemented!() }
-    | { Ok(()) }
+ClassMethods -> Result<(Vec<Lexeme<StorageT>>, Vec<Method>), ()>:
+          "SEPARATOR" ClassBody { $2 }
+    | { Ok((vec![], vec![])) }
+    ;
+// A class body is an optional list of instance variables followed by methods. We can't simply
+// use NameDefs here as "|" is also a valid method name: by spelling out both alternatives, the
+// parser can defer the decision until it has seen whether "| ID" is followed by "=".
+ClassBody -> Result<(Vec<Lexeme<StorageT>>, Vec<Method>), ()>:
+      "|" IdListOpt "|" MethodsOpt { Ok(($2?, $4?)) }
+    | MethodsOpt { Ok((vec![], $1?)) }
     ;
 Method -> Result<Method, ()>:
       MethodName "=" MethodBody
       { Ok(Method{ name: $1?, body: $3? }) }
+    | "|" "ID" "=" MethodBody
+      {
+          Ok(Method{ name: MethodName::BinaryOp(map_err($1)?, Some(map_err($2)?)), body: $4? })
+      }
     ;
 NameDefs -> Result<Vec<Lexeme<StorageT>>, ()>:
       "|" IdListOpt "|" { Ok($2?) }
@@ -41,8 +57,8 @@ MethodNameKeywords -> Result<Vec<(Lexeme<StorageT>, Lexeme<StorageT>)>, ()>:
 MethodNameBin -> Result<MethodName, ()>:
       MethodNameBinOp Argument { Ok(MethodName::BinaryOp($1?, $2?)) };
 // We'd like to just use BinOp here, rather than introducing MethodNameBinOp,
-// but then the "|" symbol conflicts with NameDefs. In other words, you can't
-// have "|" as a method name in SOM.
+// but then the "|" symbol conflicts with NameDefs: "|" methods are instead
+// handled directly in Method.
 MethodNameBinOp -> Result<Lexeme<StorageT>, ()>:
       "BINOPSEQ" { Ok(map_err($1)?) }
     | "~" { Ok(map_err($1)?) }
@@ -61,7 +77,9 @@ MethodNameBinOp -> Result<Lexeme<StorageT>, ()>:
     | "%" { Ok(map_err($1)?) }
     ;
 MethodBody -> Result<MethodBody, ()>:
-      "PRIMITIVE" { Ok(MethodBody::Primitive) }
+      "PRIMITIVE" { Ok(MethodBody::Primitive{ fallback: None }) }
+    | "PRIMITIVE" "(" NameDefs BlockExprs ")"
+      { Ok(MethodBody::Primitive{ fallback: Some(($3?, $4?)) }) }
     | "(" NameDefs BlockExprs ")" { Ok(MethodBody::Body{ vars: $2?, exprs: $3? }) }
     ;
 BlockExprs -> Result<Vec<Expr>, ()>:
@@ -160,8 +178,8 @@ Argument -> Result<Option<Lexeme<StorageT>>, ()>:
 StringConst -> Result<Expr, ()>:
       "#" "STRING" { unimplemented!() }
     | "#" "ID" { Ok(Expr::Symbol(map_err($2)?)) }
-    | "#" "KEYWORD" { unimplemented!() }
-    | "#" BinOp { unimplemented!() }
+    | "#" "KEYWORD" { Ok(Expr::Symbol(map_err($2)?)) }
+    | "#" BinOp { Ok(Expr::Symbol($2?)) }
     ;
 ArrayConst -> Result<(), ()>:
       "#" "(" ArrayList ")" { unimplemented!() };
@@ -2,22 +2,28 @@
 
 use std::{
     cell::UnsafeCell,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
     path::{Path, PathBuf},
     process,
+    time::Instant,
 };
 
 use abgc::{Gc, GcLayout};
 
 use crate::{
     compiler::{
-        compile,
-        instrs::{Builtin, Instr, Primitive},
+        compile, compile_method,
+        instrs::{Builtin, Instr, Primitive, QuickOp},
     },
     vm::{
-        objects::{Block, BlockInfo, Class, Double, Inst, Method, MethodBody, ObjType, String_},
+        inline_cache::{InlineCache, InlineCacheStats, MAX_PIC_ENTRIES},
+        objects::{
+            ArbInt, Array, Block, BlockInfo, Class, Dictionary, Double, Fraction, Inst, Method,
+            MethodBody, ObjType, ReadStream, String_, Symbol, Vector, WriteStream,
+        },
         somstack::SOMStack,
-        val::Val,
+        val::{Val, ValKind},
     },
 };
 
@@ -29,21 +35,36 @@ pub enum VMError {
     CantRepresentAsBigInt,
     /// A value which can't be represented in an `f64`.
     CantRepresentAsDouble,
+    /// A value which can't be represented in an `i32`.
+    CantRepresentAsI32,
     /// A value which can't be represented in an `isize`.
     CantRepresentAsIsize,
     /// A value which can't be represented in an `usize`.
     CantRepresentAsUsize,
+    /// Source code compiled at run time (e.g. via `addMethod:source:`) contained errors.
+    CompileError(String),
     DivisionByZero,
     /// A value which is mathematically undefined.
     DomainError,
-    /// The VM is trying to exit.
-    Exit,
+    /// Tried to remove an element from an empty collection.
+    EmptyCollection,
+    /// Tried to perform a non-local return from a block whose enclosing method has already
+    /// returned.
+    EscapedBlock,
+    /// The VM is trying to exit with the specified exit code.
+    Exit(i32),
     /// Tried to perform a `Val::downcast` operation on a non-boxed `Val`. Note that `expected`
     /// and `got` can reference the same `ObjType`.
     GcBoxTypeError {
         expected: ObjType,
         got: ObjType,
     },
+    /// Tried to index a collection (including an object's instance variables) with an index
+    /// outside the range `1..=max`. Note that, as is traditional in SOM, `tried` is 1-based.
+    IndexError {
+        tried: isize,
+        max: usize,
+    },
     /// Tried to access a global before it being initialised.
     InvalidSymbol,
     /// Tried to do a shl or shr with a value below zero.
@@ -55,15 +76,34 @@ pub enum VMError {
     },
     /// Something went wrong when trying to execute a primitive.
     PrimitiveError,
+    /// A numeric primitive was executed with a receiver which is not a number. Unlike
+    /// `NotANumber`, which refers to an argument, this never causes `adaptToNumber:andSend:` to be
+    /// sent.
+    ReceiverNotANumber {
+        got: ObjType,
+    },
     /// Tried to do a shl that would overflow memory and/or not fit in the required integer size.
     ShiftTooBig,
+    /// The SOM stack does not have enough space to execute a method or block (e.g. because of
+    /// unbounded recursion).
+    StackOverflow,
     /// A dynamic type error.
     TypeError {
         expected: ObjType,
         got: ObjType,
     },
+    /// An unknown instance variable.
+    UnknownInstVar(String),
     /// An unknown method.
     UnknownMethod(String),
+    /// Tried to perform a non-local return out of SOM code which was called from a primitive
+    /// (e.g. from the comparison block passed to `sort:`).
+    UnsupportedNonLocalReturn,
+    /// Tried to send a message with the wrong number of arguments (e.g. via `perform:`).
+    WrongNumberOfArgs {
+        wanted: usize,
+        got: usize,
+    },
 }
 
 #[derive(Debug)]
@@ -77,6 +117,23 @@ enum SendReturn {
     Val,
 }
 
+/// If sends whose method is the primitive `prim` can be quickened, return the operation of the
+/// quickened send.
+fn quick_op(prim: Primitive) -> Option<QuickOp> {
+    match prim {
+        Primitive::Add => Some(QuickOp::AddInt),
+        Primitive::Equals => Some(QuickOp::EqInt),
+        Primitive::GreaterThan => Some(QuickOp::GtInt),
+        Primitive::GreaterThanEquals => Some(QuickOp::GteInt),
+        Primitive::LessThan => Some(QuickOp::LtInt),
+        Primitive::LessThanEquals => Some(QuickOp::LteInt),
+        Primitive::Mul => Some(QuickOp::MulInt),
+        Primitive::NotEquals => Some(QuickOp::NeInt),
+        Primitive::Sub => Some(QuickOp::SubInt),
+        _ => None,
+    }
+}
+
 /// A convenience macro for use in the `exec_*` functions.
 macro_rules! stry {
     ($elem:expr) => {{
@@ -88,38 +145,73 @@ macro_rules! stry {
     }};
 }
 
+/// Options which alter the VM's behaviour.
+#[derive(Debug, Default)]
+pub struct VMOptions {
+    /// If `true`, `Integer / Integer` produces an exact `Fraction` when the result is not a whole
+    /// number. If `false` (the default, for compatibility with other SOM implementations), the
+    /// result is truncated towards zero.
+    pub fractions: bool,
+}
+
 /// The core VM struct. Although SOM is single-threaded, we roughly model what a multi-threaded VM
 /// would need to look like. That is, since this struct would need to be shared between threads and
 /// called without a single lock, thread-safety would need to be handled internally. We model that
 /// with [`UnsafeCell`].
 pub struct VM {
     classpath: Vec<String>,
+    opts: VMOptions,
+    pub array_cls: Val,
     pub block_cls: Val,
     pub block2_cls: Val,
     pub block3_cls: Val,
     pub bool_cls: Val,
     pub cls_cls: Val,
+    pub dict_cls: Val,
     pub double_cls: Val,
     pub false_cls: Val,
+    pub fraction_cls: Val,
     pub int_cls: Val,
+    pub method_cls: Val,
     pub nil_cls: Val,
     pub obj_cls: Val,
+    pub primitive_cls: Val,
+    pub read_stream_cls: Val,
     pub str_cls: Val,
     pub sym_cls: Val,
     pub system_cls: Val,
     pub true_cls: Val,
+    pub vector_cls: Val,
+    pub write_stream_cls: Val,
     pub false_: Val,
     pub nil: Val,
     pub system: Val,
     pub true_: Val,
     blockinfos: UnsafeCell<Vec<BlockInfo>>,
-    globals: UnsafeCell<HashMap<usize, Val>>,
-    inline_caches: UnsafeCell<Vec<Option<(Val, Gc<Method>)>>>,
+    /// The global variables, each of which is a cell which is `None` until the global is first
+    /// assigned. Cells are never removed, so `Instr::Global` can refer directly to a cell's index.
+    globals: UnsafeCell<Vec<Option<Val>>>,
+    /// reverse_globals maps a global's name to the index of its cell in `globals`.
+    reverse_globals: UnsafeCell<HashMap<String, usize>>,
+    inline_caches: UnsafeCell<Vec<InlineCache>>,
+    /// The global method cache, used when a send site's inline cache misses and by megamorphic
+    /// send sites. This maps `(class identity, selector)` to `(class, method)`: the class is
+    /// stored so that it cannot be collected (and its identity reused by another class) while it
+    /// is in the cache.
+    method_cache: UnsafeCell<HashMap<(usize, usize), (Val, Val)>>,
+    ic_stats: UnsafeCell<InlineCacheStats>,
+    /// The indexes of `Send` instructions which have been rewritten into quickened sends.
+    quickened: UnsafeCell<HashSet<usize>>,
     instrs: UnsafeCell<Vec<Instr>>,
-    sends: UnsafeCell<Vec<(String, usize)>>,
-    /// reverse_sends is an optimisation allowing us to reuse sends: it maps a send `(String,
+    /// The sends in the program, each of which is a `(selector, number of arguments)` pair.
+    sends: UnsafeCell<Vec<(usize, usize)>>,
+    /// reverse_sends is an optimisation allowing us to reuse sends: it maps a send `(usize,
     /// usize)` to a `usize` where the latter represents the index of the send in `sends`.
-    reverse_sends: UnsafeCell<HashMap<(String, usize), usize>>,
+    reverse_sends: UnsafeCell<HashMap<(usize, usize), usize>>,
+    /// The name of each selector: a selector's index in this vector is its ID.
+    selectors: UnsafeCell<Vec<String>>,
+    /// reverse_selectors maps a selector's name to its ID.
+    reverse_selectors: UnsafeCell<HashMap<String, usize>>,
     stack: UnsafeCell<SOMStack>,
     strings: UnsafeCell<Vec<Val>>,
     symbols: UnsafeCell<Vec<Val>>,
@@ -128,10 +220,12 @@ pub struct VM {
     reverse_strings: UnsafeCell<HashMap<String, usize>>,
     reverse_symbols: UnsafeCell<HashMap<String, usize>>,
     frames: UnsafeCell<Vec<Frame>>,
+    /// When was this VM created? Used as the epoch for `System>>time` and `System>>ticks`.
+    start_time: Instant,
 }
 
 impl VM {
-    pub fn new(classpath: Vec<String>) -> Self {
+    pub fn new(classpath: Vec<String>, opts: VMOptions) -> Self {
         // The bootstrapping phase is delicate: we need to bootstrap the Object, Class, and Nil
         // classes before we can create basic objects like nil. We thus perform bootstrapping in
         // two phases: the "very delicate" phase (with very strict rules on what is possible)
@@ -140,36 +234,52 @@ impl VM {
 
         let mut vm = VM {
             classpath,
+            opts,
+            array_cls: Val::illegal(),
             block_cls: Val::illegal(),
             bool_cls: Val::illegal(),
             block2_cls: Val::illegal(),
             block3_cls: Val::illegal(),
             cls_cls: Val::illegal(),
+            dict_cls: Val::illegal(),
             double_cls: Val::illegal(),
             false_cls: Val::illegal(),
+            fraction_cls: Val::illegal(),
             int_cls: Val::illegal(),
+            method_cls: Val::illegal(),
             nil_cls: Val::illegal(),
             obj_cls: Val::illegal(),
+            primitive_cls: Val::illegal(),
+            read_stream_cls: Val::illegal(),
             str_cls: Val::illegal(),
             sym_cls: Val::illegal(),
             system_cls: Val::illegal(),
             true_cls: Val::illegal(),
+            vector_cls: Val::illegal(),
+            write_stream_cls: Val::illegal(),
             false_: Val::illegal(),
             nil: Val::illegal(),
             system: Val::illegal(),
             true_: Val::illegal(),
             blockinfos: UnsafeCell::new(Vec::new()),
-            globals: UnsafeCell::new(HashMap::new()),
+            globals: UnsafeCell::new(Vec::new()),
+            reverse_globals: UnsafeCell::new(HashMap::new()),
             inline_caches: UnsafeCell::new(Vec::new()),
+            method_cache: UnsafeCell::new(HashMap::new()),
+            ic_stats: UnsafeCell::new(InlineCacheStats::default()),
+            quickened: UnsafeCell::new(HashSet::new()),
             instrs: UnsafeCell::new(Vec::new()),
             sends: UnsafeCell::new(Vec::new()),
             reverse_sends: UnsafeCell::new(HashMap::new()),
+            selectors: UnsafeCell::new(Vec::new()),
+            reverse_selectors: UnsafeCell::new(HashMap::new()),
             stack: UnsafeCell::new(SOMStack::new()),
             strings: UnsafeCell::new(Vec::new()),
             reverse_strings: UnsafeCell::new(HashMap::new()),
             symbols: UnsafeCell::new(Vec::new()),
             reverse_symbols: UnsafeCell::new(HashMap::new()),
             frames: UnsafeCell::new(Vec::new()),
+            start_time: Instant::now(),
         };
         // The very delicate phase.
         //
@@ -183,21 +293,26 @@ impl VM {
         // The slightly delicate phase.
         //
         // Nothing in this phase must store references to any classes earlier than it in the phase.
+        vm.array_cls = vm.init_builtin_class("Array", false);
         vm.block_cls = vm.init_builtin_class("Block", false);
         vm.block2_cls = vm.init_builtin_class("Block2", false);
         vm.block3_cls = vm.init_builtin_class("Block3", false);
         vm.bool_cls = vm.init_builtin_class("Boolean", false);
+        vm.dict_cls = vm.init_builtin_class("Dictionary", false);
         vm.double_cls = vm.init_builtin_class("Double", false);
         vm.false_cls = vm.init_builtin_class("False", false);
+        vm.fraction_cls = vm.init_builtin_class("Fraction", false);
         vm.int_cls = vm.init_builtin_class("Integer", false);
+        vm.method_cls = vm.init_builtin_class("Method", false);
+        vm.primitive_cls = vm.init_builtin_class("Primitive", false);
+        vm.read_stream_cls = vm.init_builtin_class("ReadStream", false);
         vm.str_cls = vm.init_builtin_class("String", false);
         vm.sym_cls = vm.init_builtin_class("Symbol", false);
         vm.system_cls = vm.init_builtin_class("System", false);
         vm.true_cls = vm.init_builtin_class("True", false);
-        unsafe { &mut *vm.globals.get() }.insert(
-            vm.add_symbol("system".to_string()),
-            Inst::new(&vm, vm.system_cls.clone()),
-        );
+        vm.vector_cls = vm.init_builtin_class("Vector", false);
+        vm.write_stream_cls = vm.init_builtin_class("WriteStream", false);
+        vm.set_global("system", Inst::new(&vm, vm.system_cls.clone()));
         vm.false_ = Inst::new(&vm, vm.false_cls.clone());
         vm.system = Inst::new(&vm, vm.system_cls.clone());
         vm.true_ = Inst::new(&vm, vm.true_cls.clone());
@@ -209,10 +324,15 @@ impl VM {
     /// builtin classes which do not lead to run-time instances of `Inst`.
     pub fn compile(&self, path: &Path, inst_vars_allowed: bool) -> Val {
         let cls = compile(self, path);
-        if !inst_vars_allowed && cls.num_inst_vars > 0 {
+        if !inst_vars_allowed && !cls.inst_vars.is_empty() {
             panic!("No instance vars allowed in {}", path.to_str().unwrap());
         }
-        Val::from_obj(self, cls)
+        let cls_val = Val::from_obj(self, cls);
+        cls_val
+            .downcast::<Class>(self)
+            .unwrap()
+            .bind_methods(self, &cls_val);
+        cls_val
     }
 
     fn find_class(&self, name: &str) -> Result<PathBuf, ()> {
@@ -235,14 +355,29 @@ impl VM {
             .unwrap_or_else(|_| panic!("Can't find builtin class '{}'", name));
 
         let val = self.compile(&path, inst_vars_allowed);
-        let idx = self.add_symbol(name.to_string());
-        unsafe { &mut *self.globals.get() }
-            .entry(idx)
-            .or_insert(val.clone());
+        let idx = self.add_global(name);
+        let cell = &mut unsafe { &mut *self.globals.get() }[idx];
+        if cell.is_none() {
+            *cell = Some(val.clone());
+        }
 
         val
     }
 
+    /// Return the class `name`, compiling it from the classpath (and registering it as a global)
+    /// if it hasn't already been loaded. Returns `None` if no such class can be found.
+    pub fn load_class(&self, name: &str) -> Option<Val> {
+        if let Some(v) = self.get_global(name) {
+            if v.try_downcast::<Class>(self).is_some() {
+                return Some(v);
+            }
+        }
+        let path = self.find_class(name).ok()?;
+        let cls = self.compile(&path, true);
+        self.set_global(name, cls.clone());
+        Some(cls)
+    }
+
     /// Inform the user of the error string `error` and then exit.
     pub fn error(&self, error: &str) -> ! {
         eprintln!("{}", error);
@@ -252,29 +387,73 @@ impl VM {
     /// Send the message `msg` to the receiver `rcv` with arguments `args`.
     pub fn send(&self, rcv: Val, msg: &str, args: Vec<Val>) -> Result<Val, Box<VMError>> {
         let cls = rcv.get_class(self);
-        let meth = cls.downcast::<Class>(self)?.get_method(self, msg)?;
+        let meth_val = self.lookup_method(cls, self.intern_selector(msg))?;
+        let meth: &Method = meth_val.downcast(self)?;
         match meth.body {
-            MethodBody::Primitive(_) => {
-                panic!("Primitives can't be called outside of a function frame.");
+            MethodBody::Primitive(prim) => {
+                // Primitives pop their arguments off the stack and push their result on to it, so
+                // they can only be called while a frame is active (e.g. from another primitive).
+                debug_assert!(!unsafe { &*self.frames.get() }.is_empty());
+                if unsafe { &*self.stack.get() }.remaining_capacity() < args.len() + 1 {
+                    return Err(Box::new(VMError::StackOverflow));
+                }
+                for a in args {
+                    unsafe { &mut *self.stack.get() }.push(a);
+                }
+                match self.exec_primitive(prim, rcv) {
+                    SendReturn::ClosureReturn(_) => {
+                        Err(Box::new(VMError::UnsupportedNonLocalReturn))
+                    }
+                    SendReturn::Err(e) => Err(Box::new(*e)),
+                    SendReturn::Val => Ok(unsafe { &mut *self.stack.get() }.pop()),
+                }
+            }
+            MethodBody::PrimitiveWithFallback { .. } => {
+                debug_assert!(!unsafe { &*self.frames.get() }.is_empty());
+                if unsafe { &*self.stack.get() }.remaining_capacity() < args.len() + 1 {
+                    return Err(Box::new(VMError::StackOverflow));
+                }
+                let nargs = args.len();
+                for a in args {
+                    unsafe { &mut *self.stack.get() }.push(a);
+                }
+                match self.exec_method(rcv, meth, nargs) {
+                    SendReturn::ClosureReturn(_) => {
+                        Err(Box::new(VMError::UnsupportedNonLocalReturn))
+                    }
+                    SendReturn::Err(e) => Err(Box::new(*e)),
+                    SendReturn::Val => Ok(unsafe { &mut *self.stack.get() }.pop()),
+                }
             }
             MethodBody::User {
                 num_vars,
                 bytecode_off,
                 max_stack,
+                needs_closure,
             } => {
-                if unsafe { &*self.stack.get() }.remaining_capacity() < max_stack {
-                    panic!("Not enough stack space to execute method.");
+                if unsafe { &*self.stack.get() }.remaining_capacity() < max_stack + num_vars {
+                    return Err(Box::new(VMError::StackOverflow));
                 }
                 let nargs = args.len();
                 for a in args {
                     unsafe { &mut *self.stack.get() }.push(a);
                 }
-                let frame = Frame::new(self, true, rcv.clone(), None, num_vars, nargs);
+                let frame = Frame::new(
+                    self,
+                    true,
+                    rcv.clone(),
+                    None,
+                    num_vars,
+                    nargs,
+                    needs_closure,
+                );
                 unsafe { &mut *self.frames.get() }.push(frame);
                 let r = self.exec_user(rcv, bytecode_off);
-                self.frame_pop();
+                self.frame_pop(&r);
                 match r {
-                    SendReturn::ClosureReturn(_) => unimplemented!(),
+                    SendReturn::ClosureReturn(_) => {
+                        Err(Box::new(VMError::UnsupportedNonLocalReturn))
+                    }
                     SendReturn::Err(e) => Err(Box::new(*e)),
                     SendReturn::Val => Ok(unsafe { &mut *self.stack.get() }.pop()),
                 }
@@ -302,7 +481,7 @@ impl VM {
                     unsafe { &mut *self.stack.get() }.push(Block::new(
                         self,
                         blkinfo_off,
-                        Gc::clone(&self.current_frame().closure),
+                        self.current_frame().own_closure(),
                         num_params,
                     ));
                     pc = bytecode_end;
@@ -328,21 +507,28 @@ impl VM {
                     for (frame_depth, pframe) in
                         unsafe { &*self.frames.get() }.iter().rev().enumerate()
                     {
-                        if Gc::ptr_eq(&parent_closure, &pframe.closure) {
-                            unsafe { &mut *self.stack.get() }.truncate(pframe.sp());
-                            unsafe { &mut *self.stack.get() }.push(v);
-                            return SendReturn::ClosureReturn(frame_depth);
+                        // A method which is the target of a non-local return always has its own
+                        // closure, so frames which store their variables on the stack can be
+                        // skipped.
+                        if let FrameVars::Closure(c) = &pframe.vars {
+                            if Gc::ptr_eq(&parent_closure, c) {
+                                unsafe { &mut *self.stack.get() }.truncate(pframe.sp());
+                                unsafe { &mut *self.stack.get() }.push(v);
+                                return SendReturn::ClosureReturn(frame_depth);
+                            }
                         }
                     }
-                    panic!("Return from escaped block");
+                    return SendReturn::Err(Box::new(VMError::EscapedBlock));
                 }
                 Instr::Double(i) => {
                     unsafe { &mut *self.stack.get() }.push(Double::new(self, i));
                     pc += 1;
                 }
-                Instr::Global(symbol_off) => {
-                    debug_assert!(unsafe { &*self.symbols.get() }.len() > symbol_off);
-                    if let Some(global) = unsafe { &mut *self.globals.get() }.get(&symbol_off) {
+                Instr::Global(global_idx) => {
+                    debug_assert!(unsafe { &*self.globals.get() }.len() > global_idx);
+                    if let Some(global) =
+                        unsafe { (&*self.globals.get()).get_unchecked(global_idx) }
+                    {
                         unsafe { &mut *self.stack.get() }.push(global.clone());
                     } else {
                         return SendReturn::Err(Box::new(VMError::InvalidSymbol));
@@ -350,12 +536,12 @@ impl VM {
                     pc += 1;
                 }
                 Instr::InstVarLookup(n) => {
-                    let inst: &Inst = rcv.downcast(self).unwrap();
+                    let inst: &Inst = stry!(rcv.downcast(self));
                     unsafe { &mut *self.stack.get() }.push(inst.inst_var_lookup(n));
                     pc += 1;
                 }
                 Instr::InstVarSet(n) => {
-                    let inst: &Inst = rcv.downcast(self).unwrap();
+                    let inst: &Inst = stry!(rcv.downcast(self));
                     inst.inst_var_set(n, unsafe { &*self.stack.get() }.peek());
                     pc += 1;
                 }
@@ -367,49 +553,78 @@ impl VM {
                     unsafe { &mut *self.stack.get() }.pop();
                     pc += 1;
                 }
+                Instr::QuickSend(qop, _, _) => {
+                    let (rcv, arg) = {
+                        let stack = unsafe { &*self.stack.get() };
+                        (stack.peek_n(1), stack.peek())
+                    };
+                    if rcv.valkind() != ValKind::INT || arg.valkind() != ValKind::INT {
+                        // The guard failed: turn this instruction back into a normal send and
+                        // execute that instead.
+                        self.deoptimise(pc);
+                        continue;
+                    }
+                    unsafe { &mut *self.stack.get() }.truncate_n(2);
+                    let v = match qop {
+                        QuickOp::AddInt => stry!(rcv.add(self, arg)),
+                        QuickOp::SubInt => stry!(rcv.sub(self, arg)),
+                        QuickOp::MulInt => stry!(rcv.mul(self, arg)),
+                        _ => {
+                            let lhs = rcv.as_isize(self).unwrap();
+                            let rhs = arg.as_isize(self).unwrap();
+                            Val::from_bool(
+                                self,
+                                match qop {
+                                    QuickOp::EqInt => lhs == rhs,
+                                    QuickOp::NeInt => lhs != rhs,
+                                    QuickOp::LtInt => lhs < rhs,
+                                    QuickOp::LteInt => lhs <= rhs,
+                                    QuickOp::GtInt => lhs > rhs,
+                                    QuickOp::GteInt => lhs >= rhs,
+                                    QuickOp::AddInt | QuickOp::SubInt | QuickOp::MulInt => {
+                                        unreachable!()
+                                    }
+                                },
+                            )
+                        }
+                    };
+                    unsafe { &mut *self.stack.get() }.push(v);
+                    pc += 1;
+                }
                 Instr::Return => {
                     return SendReturn::Val;
                 }
                 Instr::Send(send_idx, cache_idx) => {
-                    let (rcv, nargs, meth) = {
+                    let (rcv, nargs, meth_val) = {
                         debug_assert!(send_idx < unsafe { &*self.sends.get() }.len());
-                        let (ref name, nargs) =
-                            unsafe { (&*self.sends.get()).get_unchecked(send_idx) };
-                        // Note that since we maintain a reference to `name` for the rest of this
-                        // block, we mustn't mutate (directly or indirectly) `self.sends` in any
-                        // way.
-                        let rcv = unsafe { &mut *self.stack.get() }.pop_n(*nargs);
+                        let (sel, nargs) = *unsafe { (&*self.sends.get()).get_unchecked(send_idx) };
+                        let rcv = unsafe { &mut *self.stack.get() }.pop_n(nargs);
                         let rcv_cls = rcv.get_class(self);
 
-                        let meth = stry!(self.inline_cache_lookup(cache_idx, rcv_cls, name));
+                        let meth = stry!(self.inline_cache_lookup(cache_idx, sel, rcv_cls));
                         (rcv, nargs, meth)
                     };
 
                     self.current_frame()
                         .set_sp(unsafe { &*self.stack.get() }.len() - nargs);
-                    let r = match meth.body {
+                    let meth: &Method = stry!(meth_val.downcast(self));
+                    match meth.body {
                         MethodBody::Primitive(Primitive::Restart) => {
                             unsafe { &mut *self.stack.get() }.truncate(stack_start);
                             pc = meth_start_pc;
                             continue;
                         }
-                        MethodBody::Primitive(p) => self.exec_primitive(p, rcv),
-                        MethodBody::User {
-                            num_vars,
-                            bytecode_off,
-                            max_stack,
-                        } => {
-                            if unsafe { &*self.stack.get() }.remaining_capacity() < max_stack {
-                                panic!("Not enough stack space to execute method.");
+                        MethodBody::Primitive(prim) if nargs == 1 => {
+                            if let Some(qop) = quick_op(prim) {
+                                let arg = unsafe { &*self.stack.get() }.peek();
+                                if rcv.valkind() == ValKind::INT && arg.valkind() == ValKind::INT {
+                                    self.quicken(pc, qop);
+                                }
                             }
-                            let nframe =
-                                Frame::new(self, true, rcv.clone(), None, num_vars, *nargs);
-                            unsafe { &mut *self.frames.get() }.push(nframe);
-                            let r = self.exec_user(rcv, bytecode_off);
-                            self.frame_pop();
-                            r
                         }
-                    };
+                        _ => (),
+                    }
+                    let r = self.exec_method(rcv, meth, nargs);
                     match r {
                         SendReturn::ClosureReturn(d) => {
                             if d > 0 {
@@ -436,24 +651,200 @@ impl VM {
                     pc += 1;
                 }
                 Instr::VarLookup(d, n) => {
-                    let val = self.current_frame().var_lookup(d, n);
+                    let val = self.current_frame().var_lookup(self, d, n);
                     unsafe { &mut *self.stack.get() }.push(val);
                     pc += 1;
                 }
                 Instr::VarSet(d, n) => {
                     let val = unsafe { &*self.stack.get() }.peek();
-                    self.current_frame().var_set(d, n, val);
+                    self.current_frame().var_set(self, d, n, val);
                     pc += 1;
                 }
             }
         }
     }
 
+    /// Execute the method `meth` with receiver `rcv` and `nargs` arguments (which must already
+    /// have been pushed onto the stack).
+    fn exec_method(&self, rcv: Val, meth: &Method, nargs: usize) -> SendReturn {
+        match meth.body {
+            MethodBody::Primitive(p) => self.exec_primitive(p, rcv),
+            MethodBody::PrimitiveWithFallback {
+                prim,
+                num_vars,
+                bytecode_off,
+                max_stack,
+                needs_closure,
+            } => {
+                // The primitive may pop some or all of its arguments before failing, so we keep
+                // a copy of them in case the fallback code needs to be run.
+                let stack = unsafe { &mut *self.stack.get() };
+                let args_start = stack.len() - nargs;
+                let mut args = Vec::with_capacity(nargs);
+                for _ in 0..nargs {
+                    args.push(stack.pop());
+                }
+                for a in args.iter().rev() {
+                    stack.push(a.clone());
+                }
+                match self.exec_primitive(prim, rcv.clone()) {
+                    SendReturn::Err(e) => {
+                        // `system exit:` is implemented as an error, but is not a failure.
+                        if let VMError::Exit(_) = *e {
+                            return SendReturn::Err(e);
+                        }
+                        let stack = unsafe { &mut *self.stack.get() };
+                        stack.truncate(args_start);
+                        for a in args.into_iter().rev() {
+                            stack.push(a);
+                        }
+                        self.exec_user_method(
+                            rcv,
+                            num_vars,
+                            bytecode_off,
+                            max_stack,
+                            needs_closure,
+                            nargs,
+                        )
+                    }
+                    r => r,
+                }
+            }
+            MethodBody::User {
+                num_vars,
+                bytecode_off,
+                max_stack,
+                needs_closure,
+            } => {
+                self.exec_user_method(rcv, num_vars, bytecode_off, max_stack, needs_closure, nargs)
+            }
+        }
+    }
+
+    /// Execute the user bytecode at `bytecode_off` as a method with receiver `rcv` and `nargs`
+    /// arguments (which must already have been pushed onto the stack).
+    fn exec_user_method(
+        &self,
+        rcv: Val,
+        num_vars: usize,
+        bytecode_off: usize,
+        max_stack: usize,
+        needs_closure: bool,
+        nargs: usize,
+    ) -> SendReturn {
+        if unsafe { &*self.stack.get() }.remaining_capacity() < max_stack + num_vars {
+            return SendReturn::Err(Box::new(VMError::StackOverflow));
+        }
+        let nframe = Frame::new(
+            self,
+            true,
+            rcv.clone(),
+            None,
+            num_vars,
+            nargs,
+            needs_closure,
+        );
+        unsafe { &mut *self.frames.get() }.push(nframe);
+        let r = self.exec_user(rcv, bytecode_off);
+        self.frame_pop(&r);
+        r
+    }
+
+    /// Send the message `sel` (which must be a `String_` or `Symbol`) with arguments `args` to
+    /// `rcv`. If `cls` is `Some`, the method is looked up in that class rather than `rcv`'s class.
+    fn perform(&self, rcv: Val, sel: Val, args: Vec<Val>, cls: Option<Val>) -> SendReturn {
+        let sel_str = stry!(String_::from_val(self, &sel));
+        let cls = cls.unwrap_or_else(|| rcv.get_class(self));
+        let meth_val = stry!(self.lookup_method(cls, self.intern_selector(sel_str.as_str())));
+        self.invoke(rcv, stry!(meth_val.downcast(self)), args)
+    }
+
+    /// Execute the method `meth` with receiver `rcv` and arguments `args`.
+    fn invoke(&self, rcv: Val, meth: &Method, args: Vec<Val>) -> SendReturn {
+        let wanted = meth.num_args();
+        if wanted != args.len() {
+            return SendReturn::Err(Box::new(VMError::WrongNumberOfArgs {
+                wanted,
+                got: args.len(),
+            }));
+        }
+        if unsafe { &*self.stack.get() }.remaining_capacity() < args.len() {
+            return SendReturn::Err(Box::new(VMError::StackOverflow));
+        }
+        let nargs = args.len();
+        for a in args {
+            unsafe { &mut *self.stack.get() }.push(a);
+        }
+        self.exec_method(rcv, meth, nargs)
+    }
+
+    /// Execute a numeric primitive whose selector is `sel` by applying `op` to `rcv` and the
+    /// argument on top of the stack. If `op` fails because the argument is not a built-in number,
+    /// the argument is given the chance to perform the operation itself: see `adapt_to_number`.
+    fn exec_number_prim<F>(&self, rcv: Val, sel: &str, op: F) -> SendReturn
+    where
+        F: FnOnce(&Val, Val) -> Result<Val, Box<VMError>>,
+    {
+        let arg = unsafe { &mut *self.stack.get() }.pop();
+        let v = match op(&rcv, arg.clone()) {
+            Ok(v) => v,
+            Err(e) => stry!(self.adapt_to_number(rcv, sel, arg, e)),
+        };
+        unsafe { &mut *self.stack.get() }.push(v);
+        SendReturn::Val
+    }
+
+    /// Called when the numeric primitive `sel`, applied to `rcv` and `arg`, has failed with
+    /// `err`. If `err` is because `arg` is not a built-in number, and `arg` understands
+    /// `adaptToNumber:andSend:`, this sends `arg adaptToNumber: rcv andSend: sel` and returns
+    /// the result. Otherwise `err` is returned.
+    fn adapt_to_number(
+        &self,
+        rcv: Val,
+        sel: &str,
+        arg: Val,
+        err: Box<VMError>,
+    ) -> Result<Val, Box<VMError>> {
+        if let VMError::NotANumber { .. } = *err {
+            let cls = arg.get_class(self);
+            let adapt_sel = self.intern_selector("adaptToNumber:andSend:");
+            if self.lookup_method(cls, adapt_sel).is_ok() {
+                let sel = self.intern_symbol(sel.to_owned());
+                return self.send(arg, "adaptToNumber:andSend:", vec![rcv, sel]);
+            }
+        }
+        Err(err)
+    }
+
     fn exec_primitive(&self, prim: Primitive, rcv: Val) -> SendReturn {
         match prim {
-            Primitive::Add => {
-                unsafe { &mut *self.stack.get() }
-                    .push(stry!(rcv.add(self, unsafe { &mut *self.stack.get() }.pop())));
+            Primitive::Abs => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(Double::new(self, d.abs()));
+                SendReturn::Val
+            }
+            Primitive::Add => self.exec_number_prim(rcv, "+", |rcv, arg| rcv.add(self, arg)),
+            Primitive::AddMethod => {
+                let src = unsafe { &mut *self.stack.get() }.pop();
+                let sel = unsafe { &mut *self.stack.get() }.pop();
+                let cls: &Class = stry!(rcv.downcast(self));
+                let src_str = stry!(String_::from_val(self, &src));
+                let sel_str = stry!(String_::from_val(self, &sel));
+                let meth = match compile_method(self, cls, src_str.as_str()) {
+                    Ok(m) => m,
+                    Err(e) => return SendReturn::Err(Box::new(VMError::CompileError(e))),
+                };
+                let name = &stry!(meth.downcast::<Method>(self)).name;
+                if name != sel_str.as_str() {
+                    return SendReturn::Err(Box::new(VMError::CompileError(format!(
+                        "Method '{}' does not match selector '{}'",
+                        name,
+                        sel_str.as_str()
+                    ))));
+                }
+                stry!(cls.add_method(self, &rcv, meth.clone()));
+                self.flush_inline_caches();
+                unsafe { &mut *self.stack.get() }.push(meth);
                 SendReturn::Val
             }
             Primitive::And => {
@@ -461,13 +852,70 @@ impl VM {
                     .push(stry!(rcv.and(self, unsafe { &mut *self.stack.get() }.pop())));
                 SendReturn::Val
             }
+            Primitive::Append => {
+                let val = unsafe { &mut *self.stack.get() }.pop();
+                let vec: &Vector = stry!(rcv.downcast(self));
+                vec.append(val.clone());
+                unsafe { &mut *self.stack.get() }.push(val);
+                SendReturn::Val
+            }
+            Primitive::AsArray => {
+                let vec: &Vector = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(vec.to_array(self));
+                SendReturn::Val
+            }
+            Primitive::AsDouble => {
+                unsafe { &mut *self.stack.get() }.push(stry!(rcv.to_doubleval(self)));
+                SendReturn::Val
+            }
+            Primitive::AsInteger => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(stry!(Double::f64_to_int(self, d.trunc())));
+                SendReturn::Val
+            }
+            Primitive::AsLowercase => {
+                let str_ = stry!(String_::from_val(self, &rcv));
+                unsafe { &mut *self.stack.get() }.push(str_.to_lowercase(self));
+                SendReturn::Val
+            }
             Primitive::AsString => {
                 unsafe { &mut *self.stack.get() }.push(stry!(rcv.to_strval(self)));
                 SendReturn::Val
             }
             Primitive::AsSymbol => {
                 unsafe { &mut *self.stack.get() }
-                    .push(stry!(stry!(rcv.downcast::<String_>(self)).to_symbol(self)));
+                    .push(stry!(String_::from_val(self, &rcv)).to_symbol(self));
+                SendReturn::Val
+            }
+            Primitive::AsUppercase => {
+                let str_ = stry!(String_::from_val(self, &rcv));
+                unsafe { &mut *self.stack.get() }.push(str_.to_uppercase(self));
+                SendReturn::Val
+            }
+            Primitive::At => {
+                let arr: &Array = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }
+                    .push(stry!(arr.at(self, unsafe { &mut *self.stack.get() }.pop())));
+                SendReturn::Val
+            }
+            Primitive::AtPut => {
+                let val = unsafe { &mut *self.stack.get() }.pop();
+                let idx = unsafe { &mut *self.stack.get() }.pop();
+                let arr: &Array = stry!(rcv.downcast(self));
+                stry!(arr.at_put(self, idx, val.clone()));
+                unsafe { &mut *self.stack.get() }.push(val);
+                SendReturn::Val
+            }
+            Primitive::AtEnd => {
+                let rs: &ReadStream = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(Val::from_bool(self, rs.at_end(self)));
+                SendReturn::Val
+            }
+            Primitive::BeginsWith => {
+                let str_ = stry!(String_::from_val(self, &rcv));
+                unsafe { &mut *self.stack.get() }.push(stry!(
+                    str_.begins_with(self, unsafe { &mut *self.stack.get() }.pop())
+                ));
                 SendReturn::Val
             }
             Primitive::BitXor => {
@@ -475,23 +923,95 @@ impl VM {
                     .push(stry!(rcv.xor(self, unsafe { &mut *self.stack.get() }.pop())));
                 SendReturn::Val
             }
+            Primitive::Ceiling => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(stry!(Double::f64_to_int(self, d.ceil())));
+                SendReturn::Val
+            }
             Primitive::Class => {
                 unsafe { &mut *self.stack.get() }.push(rcv.get_class(self));
                 SendReturn::Val
             }
             Primitive::Concatenate => {
-                unsafe { &mut *self.stack.get() }.push(stry!(stry!(rcv.downcast::<String_>(self))
+                unsafe { &mut *self.stack.get() }.push(stry!(stry!(String_::from_val(self, &rcv))
                     .concatenate(self, unsafe { &mut *self.stack.get() }.pop())));
                 SendReturn::Val
             }
+            Primitive::Contents => {
+                let ws: &WriteStream = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(ws.contents(self));
+                SendReturn::Val
+            }
+            Primitive::ContainsKey => {
+                let key = unsafe { &mut *self.stack.get() }.pop();
+                let dict: &Dictionary = stry!(rcv.downcast(self));
+                let b = stry!(dict.contains_key(self, key));
+                unsafe { &mut *self.stack.get() }.push(Val::from_bool(self, b));
+                SendReturn::Val
+            }
+            Primitive::Cos => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(Double::new(self, d.cos()));
+                SendReturn::Val
+            }
+            Primitive::Denominator => {
+                let frac: &Fraction = stry!(rcv.downcast(self));
+                let denom = frac.ratio().denom().clone();
+                unsafe { &mut *self.stack.get() }.push(stry!(ArbInt::new(self, denom)));
+                SendReturn::Val
+            }
             Primitive::Div => {
-                unsafe { &mut *self.stack.get() }
-                    .push(stry!(rcv.div(self, unsafe { &mut *self.stack.get() }.pop())));
+                let is_int = |v: &Val| {
+                    v.as_isize(self).is_some() || v.try_downcast::<ArbInt>(self).is_some()
+                };
+                self.exec_number_prim(rcv, "/", |rcv, arg| {
+                    if self.opts.fractions && is_int(rcv) && is_int(&arg) {
+                        Fraction::from_vals(self, rcv.clone(), arg)
+                    } else {
+                        rcv.div(self, arg)
+                    }
+                })
+            }
+            Primitive::DictionaryAt => {
+                let key = unsafe { &mut *self.stack.get() }.pop();
+                let dict: &Dictionary = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(stry!(dict.at(self, key)));
+                SendReturn::Val
+            }
+            Primitive::DictionaryAtPut => {
+                let val = unsafe { &mut *self.stack.get() }.pop();
+                let key = unsafe { &mut *self.stack.get() }.pop();
+                let dict: &Dictionary = stry!(rcv.downcast(self));
+                stry!(dict.at_put(self, key, val.clone()));
+                unsafe { &mut *self.stack.get() }.push(val);
+                SendReturn::Val
+            }
+            Primitive::DictionaryNew => {
+                unsafe { &mut *self.stack.get() }.push(Dictionary::new(self, rcv));
+                SendReturn::Val
+            }
+            Primitive::DictionarySize => {
+                let dict: &Dictionary = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(stry!(Val::from_usize(self, dict.size())));
                 SendReturn::Val
             }
             Primitive::DoubleDiv => {
+                self.exec_number_prim(rcv, "//", |rcv, arg| rcv.double_div(self, arg))
+            }
+            Primitive::DoubleFromString => {
+                let v = unsafe { &mut *self.stack.get() }.pop();
+                let str_ = stry!(String_::from_val(self, &v));
+                let d = match Double::parse(str_.as_str()) {
+                    Some(d) => Double::new(self, d),
+                    None => self.nil.clone(),
+                };
+                unsafe { &mut *self.stack.get() }.push(d);
+                SendReturn::Val
+            }
+            Primitive::EndsWith => {
+                let str_ = stry!(String_::from_val(self, &rcv));
                 unsafe { &mut *self.stack.get() }.push(stry!(
-                    rcv.double_div(self, unsafe { &mut *self.stack.get() }.pop())
+                    str_.ends_with(self, unsafe { &mut *self.stack.get() }.pop())
                 ));
                 SendReturn::Val
             }
@@ -503,97 +1023,398 @@ impl VM {
             }
             Primitive::Global => {
                 let name = unsafe { &mut *self.stack.get() }.pop();
-                let as_string: &String_ = stry!(name.downcast(self));
-                let s = as_string.as_str();
-
-                if let Some(i) = unsafe { &mut *self.reverse_symbols.get() }.get(s) {
-                    if let Some(val) = unsafe { &mut *self.globals.get() }.get(&i) {
-                        unsafe { &mut *self.stack.get() }.push(val.clone());
-                        return SendReturn::Val;
+                let as_string = stry!(String_::from_val(self, &name));
+                match self.get_global(as_string.as_str()) {
+                    Some(val) => {
+                        unsafe { &mut *self.stack.get() }.push(val);
+                        SendReturn::Val
                     }
+                    None => SendReturn::Err(Box::new(VMError::InvalidSymbol)),
                 }
-                SendReturn::Err(Box::new(VMError::InvalidSymbol))
             }
             Primitive::GlobalPut => {
                 let value = unsafe { &mut *self.stack.get() }.pop();
                 let name = unsafe { &mut *self.stack.get() }.pop();
-                let as_string: &String_ = stry!(name.downcast(self));
-                let s = as_string.as_str();
-
-                if let Some(i) = unsafe { &mut *self.reverse_symbols.get() }.get(s) {
-                    unsafe { &mut *self.globals.get() }.insert(*i, value.clone());
-                    unsafe { &mut *self.stack.get() }.push(value);
-                }
+                let as_string = stry!(String_::from_val(self, &name));
+                // Note that, since `nil`, `true`, and `false` are compiled to `Instr::Builtin`,
+                // assigning to globals of those names has no effect on the values of `nil` etc.
+                self.set_global(as_string.as_str(), value.clone());
+                unsafe { &mut *self.stack.get() }.push(value);
                 SendReturn::Val
             }
-            Primitive::GreaterThan => {
-                unsafe { &mut *self.stack.get() }.push(stry!(
-                    rcv.greater_than(self, unsafe { &mut *self.stack.get() }.pop())
-                ));
+            Primitive::ErrorPrint => {
+                let v = unsafe { &mut *self.stack.get() }.pop();
+                let str_ = stry!(String_::from_val(self, &v));
+                eprint!("{}", str_.as_str());
+                unsafe { &mut *self.stack.get() }.push(self.system.clone());
+                SendReturn::Val
+            }
+            Primitive::ErrorPrintln => {
+                let v = unsafe { &mut *self.stack.get() }.pop();
+                let str_ = stry!(String_::from_val(self, &v));
+                eprintln!("{}", str_.as_str());
+                unsafe { &mut *self.stack.get() }.push(self.system.clone());
+                SendReturn::Val
+            }
+            Primitive::Exit => {
+                let v = unsafe { &mut *self.stack.get() }.pop();
+                let code = match v.as_isize(self) {
+                    Some(i) => {
+                        stry!(i32::try_from(i).map_err(|_| Box::new(VMError::CantRepresentAsI32)))
+                    }
+                    None if v.try_downcast::<ArbInt>(self).is_some() => {
+                        return SendReturn::Err(Box::new(VMError::CantRepresentAsI32))
+                    }
+                    None => {
+                        return SendReturn::Err(Box::new(VMError::TypeError {
+                            expected: ObjType::Int,
+                            got: v.dyn_objtype(self),
+                        }))
+                    }
+                };
+                SendReturn::Err(Box::new(VMError::Exit(code)))
+            }
+            Primitive::Exp => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(Double::new(self, d.exp()));
                 SendReturn::Val
             }
+            Primitive::Fields => {
+                let cls: &Class = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(cls.fields(self));
+                SendReturn::Val
+            }
+            Primitive::Floor => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(stry!(Double::f64_to_int(self, d.floor())));
+                SendReturn::Val
+            }
+            Primitive::FractionNew => {
+                let denom = unsafe { &mut *self.stack.get() }.pop();
+                let numer = unsafe { &mut *self.stack.get() }.pop();
+                unsafe { &mut *self.stack.get() }
+                    .push(stry!(Fraction::from_vals(self, numer, denom)));
+                SendReturn::Val
+            }
+            Primitive::FullGC => {
+                // FIXME: this does not yet force a collection. Until it does, we report that no
+                // collection took place.
+                unsafe { &mut *self.stack.get() }.push(self.false_.clone());
+                SendReturn::Val
+            }
+            Primitive::GreaterThan => {
+                self.exec_number_prim(rcv, ">", |rcv, arg| rcv.greater_than(self, arg))
+            }
             Primitive::GreaterThanEquals => {
-                unsafe { &mut *self.stack.get() }.push(stry!(
-                    rcv.greater_than_equals(self, unsafe { &mut *self.stack.get() }.pop())
-                ));
+                self.exec_number_prim(rcv, ">=", |rcv, arg| rcv.greater_than_equals(self, arg))
+            }
+            Primitive::Halt => {
+                // yksom has no interactive debugger to hand control to.
+                SendReturn::Err(Box::new(VMError::PrimitiveError))
+            }
+            Primitive::Hashcode => {
+                unsafe { &mut *self.stack.get() }.push(stry!(rcv.hashcode(self)));
                 SendReturn::Val
             }
-            Primitive::Halt => unimplemented!(),
-            Primitive::Hashcode => unimplemented!(),
-            Primitive::Inspect => unimplemented!(),
-            Primitive::InstVarAt => unimplemented!(),
-            Primitive::InstVarAtPut => unimplemented!(),
-            Primitive::InstVarNamed => unimplemented!(),
-            Primitive::LessThan => {
-                unsafe { &mut *self.stack.get() }.push(stry!(
-                    rcv.less_than(self, unsafe { &mut *self.stack.get() }.pop())
-                ));
+            Primitive::Holder => {
+                let meth: &Method = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(meth.holder());
                 SendReturn::Val
             }
-            Primitive::LessThanEquals => {
+            Primitive::IndexOf => {
+                let str_ = stry!(String_::from_val(self, &rcv));
                 unsafe { &mut *self.stack.get() }.push(stry!(
-                    rcv.less_than_equals(self, unsafe { &mut *self.stack.get() }.pop())
+                    str_.index_of(self, unsafe { &mut *self.stack.get() }.pop())
                 ));
                 SendReturn::Val
             }
-            Primitive::Mod => {
+            Primitive::Inspect => {
+                // yksom has no interactive debugger to hand control to.
+                SendReturn::Err(Box::new(VMError::PrimitiveError))
+            }
+            Primitive::InstVarAt => {
                 unsafe { &mut *self.stack.get() }.push(stry!(
-                    rcv.modulus(self, unsafe { &mut *self.stack.get() }.pop())
+                    rcv.inst_var_at(self, unsafe { &mut *self.stack.get() }.pop())
                 ));
                 SendReturn::Val
             }
-            Primitive::Mul => {
+            Primitive::InstVarAtPut => {
+                let val = unsafe { &mut *self.stack.get() }.pop();
+                let idx = unsafe { &mut *self.stack.get() }.pop();
+                unsafe { &mut *self.stack.get() }.push(stry!(rcv.inst_var_at_put(self, idx, val)));
+                SendReturn::Val
+            }
+            Primitive::InstVarNamed => {
+                let name = unsafe { &mut *self.stack.get() }.pop();
+                let as_string = stry!(String_::from_val(self, &name));
                 unsafe { &mut *self.stack.get() }
-                    .push(stry!(rcv.mul(self, unsafe { &mut *self.stack.get() }.pop())));
+                    .push(stry!(rcv.inst_var_named(self, as_string.as_str())));
+                SendReturn::Val
+            }
+            Primitive::IntegerFromString => {
+                let v = unsafe { &mut *self.stack.get() }.pop();
+                let str_ = stry!(String_::from_val(self, &v));
+                unsafe { &mut *self.stack.get() }.push(stry!(str_.to_integer(self)));
+                SendReturn::Val
+            }
+            Primitive::InvokeOnWith => {
+                let args = unsafe { &mut *self.stack.get() }.pop();
+                let obj = unsafe { &mut *self.stack.get() }.pop();
+                let meth: &Method = stry!(rcv.downcast(self));
+                let args = stry!(args.downcast::<Array>(self)).to_vec();
+                self.invoke(obj, meth, args)
+            }
+            Primitive::IsLetters => {
+                let str_ = stry!(String_::from_val(self, &rcv));
+                unsafe { &mut *self.stack.get() }.push(str_.all_chars(self, char::is_alphabetic));
                 SendReturn::Val
             }
+            Primitive::IsWhiteSpace => {
+                let str_ = stry!(String_::from_val(self, &rcv));
+                unsafe { &mut *self.stack.get() }.push(str_.all_chars(self, char::is_whitespace));
+                SendReturn::Val
+            }
+            Primitive::Keys => {
+                let dict: &Dictionary = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(dict.keys(self));
+                SendReturn::Val
+            }
+            Primitive::Length => {
+                let arr: &Array = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(stry!(Val::from_usize(self, arr.length())));
+                SendReturn::Val
+            }
+            Primitive::LessThan => {
+                self.exec_number_prim(rcv, "<", |rcv, arg| rcv.less_than(self, arg))
+            }
+            Primitive::LessThanEquals => {
+                self.exec_number_prim(rcv, "<=", |rcv, arg| rcv.less_than_equals(self, arg))
+            }
+            Primitive::Mod => self.exec_number_prim(rcv, "%", |rcv, arg| rcv.modulus(self, arg)),
+            Primitive::IsDigits => {
+                let str_ = stry!(String_::from_val(self, &rcv));
+                unsafe { &mut *self.stack.get() }
+                    .push(str_.all_chars(self, |c| c.is_ascii_digit()));
+                SendReturn::Val
+            }
+            Primitive::IsInfinite => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(Val::from_bool(self, d.is_infinite()));
+                SendReturn::Val
+            }
+            Primitive::IsNaN => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(Val::from_bool(self, d.is_nan()));
+                SendReturn::Val
+            }
+            Primitive::Load => {
+                let name = unsafe { &mut *self.stack.get() }.pop();
+                let name_str = stry!(String_::from_val(self, &name));
+                let cls = self
+                    .load_class(name_str.as_str())
+                    .unwrap_or_else(|| self.nil.clone());
+                unsafe { &mut *self.stack.get() }.push(cls);
+                SendReturn::Val
+            }
+            Primitive::Log => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(Double::new(self, d.ln()));
+                SendReturn::Val
+            }
+            Primitive::Methods => {
+                let cls: &Class = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(cls.methods(self));
+                SendReturn::Val
+            }
+            Primitive::Mul => self.exec_number_prim(rcv, "*", |rcv, arg| rcv.mul(self, arg)),
             Primitive::Name => {
                 unsafe { &mut *self.stack.get() }
                     .push(stry!(stry!(rcv.downcast::<Class>(self)).name(self)));
                 SendReturn::Val
             }
+            Primitive::Negated => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(Double::new(self, -d));
+                SendReturn::Val
+            }
             Primitive::New => {
                 unsafe { &mut *self.stack.get() }.push(Inst::new(self, rcv));
                 SendReturn::Val
             }
+            Primitive::NewArray => {
+                let len = unsafe { &mut *self.stack.get() }.pop();
+                match len.as_usize(self) {
+                    Some(len) => {
+                        unsafe { &mut *self.stack.get() }.push(Array::new(self, len));
+                        SendReturn::Val
+                    }
+                    None => match len.dyn_objtype(self) {
+                        ObjType::ArbInt | ObjType::Int => {
+                            SendReturn::Err(Box::new(VMError::CantRepresentAsUsize))
+                        }
+                        got => SendReturn::Err(Box::new(VMError::TypeError {
+                            expected: ObjType::Int,
+                            got,
+                        })),
+                    },
+                }
+            }
             Primitive::NotEquals => {
                 unsafe { &mut *self.stack.get() }.push(stry!(
                     rcv.not_equals(self, unsafe { &mut *self.stack.get() }.pop())
                 ));
                 SendReturn::Val
             }
-            Primitive::ObjectSize => unimplemented!(),
-            Primitive::Perform => unimplemented!(),
-            Primitive::PerformInSuperClass => unimplemented!(),
-            Primitive::PerformWithArguments => unimplemented!(),
-            Primitive::PerformWithArgumentsInSuperClass => unimplemented!(),
+            Primitive::Next => {
+                let rs: &ReadStream = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(rs.next(self));
+                SendReturn::Val
+            }
+            Primitive::NextPut => {
+                let v = unsafe { &mut *self.stack.get() }.pop();
+                let ws: &WriteStream = stry!(rcv.downcast(self));
+                stry!(ws.next_put(self, v.clone()));
+                unsafe { &mut *self.stack.get() }.push(v);
+                SendReturn::Val
+            }
+            Primitive::NextPutAll => {
+                let coll = unsafe { &mut *self.stack.get() }.pop();
+                let ws: &WriteStream = stry!(rcv.downcast(self));
+                stry!(ws.next_put_all(self, coll.clone()));
+                unsafe { &mut *self.stack.get() }.push(coll);
+                SendReturn::Val
+            }
+            Primitive::NumArgs => {
+                let meth: &Method = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }
+                    .push(stry!(Val::from_usize(self, meth.num_args())));
+                SendReturn::Val
+            }
+            Primitive::Numerator => {
+                let frac: &Fraction = stry!(rcv.downcast(self));
+                let numer = frac.ratio().numer().clone();
+                unsafe { &mut *self.stack.get() }.push(stry!(ArbInt::new(self, numer)));
+                SendReturn::Val
+            }
+            Primitive::ObjectSize => {
+                unsafe { &mut *self.stack.get() }
+                    .push(stry!(Val::from_usize(self, rcv.object_size(self))));
+                SendReturn::Val
+            }
+            Primitive::Or => {
+                unsafe { &mut *self.stack.get() }
+                    .push(stry!(rcv.or(self, unsafe { &mut *self.stack.get() }.pop())));
+                SendReturn::Val
+            }
+            Primitive::Peek => {
+                let rs: &ReadStream = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(rs.peek(self));
+                SendReturn::Val
+            }
+            Primitive::Perform => {
+                let sel = unsafe { &mut *self.stack.get() }.pop();
+                self.perform(rcv, sel, vec![], None)
+            }
+            Primitive::PerformInSuperClass => {
+                let cls = unsafe { &mut *self.stack.get() }.pop();
+                let sel = unsafe { &mut *self.stack.get() }.pop();
+                self.perform(rcv, sel, vec![], Some(cls))
+            }
+            Primitive::PerformWithArguments => {
+                let args = unsafe { &mut *self.stack.get() }.pop();
+                let sel = unsafe { &mut *self.stack.get() }.pop();
+                let args = stry!(args.downcast::<Array>(self)).to_vec();
+                self.perform(rcv, sel, args, None)
+            }
+            Primitive::PerformWithArgumentsInSuperClass => {
+                let cls = unsafe { &mut *self.stack.get() }.pop();
+                let args = unsafe { &mut *self.stack.get() }.pop();
+                let sel = unsafe { &mut *self.stack.get() }.pop();
+                let args = stry!(args.downcast::<Array>(self)).to_vec();
+                self.perform(rcv, sel, args, Some(cls))
+            }
+            Primitive::RaisedTo => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                let exp = stry!(unsafe { &mut *self.stack.get() }.pop().to_doubleval(self));
+                let exp = stry!(exp.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(Double::new(self, d.powf(exp)));
+                SendReturn::Val
+            }
+            Primitive::ReadStreamOn => {
+                let coll = unsafe { &mut *self.stack.get() }.pop();
+                unsafe { &mut *self.stack.get() }.push(stry!(ReadStream::new(self, coll)));
+                SendReturn::Val
+            }
             Primitive::RefEquals => {
                 unsafe { &mut *self.stack.get() }.push(stry!(
                     rcv.ref_equals(self, unsafe { &mut *self.stack.get() }.pop())
                 ));
                 SendReturn::Val
             }
-            Primitive::Restart => unreachable!(),
+            Primitive::RespondsTo => {
+                let sel = unsafe { &mut *self.stack.get() }.pop();
+                let sel_str = stry!(String_::from_val(self, &sel));
+                let cls = rcv.get_class(self);
+                let b = self
+                    .lookup_method(cls, self.intern_selector(sel_str.as_str()))
+                    .is_ok();
+                unsafe { &mut *self.stack.get() }.push(Val::from_bool(self, b));
+                SendReturn::Val
+            }
+            Primitive::PrintStringRadix => {
+                unsafe { &mut *self.stack.get() }.push(stry!(
+                    rcv.to_strval_radix(self, unsafe { &mut *self.stack.get() }.pop())
+                ));
+                SendReturn::Val
+            }
+            Primitive::Rem => self.exec_number_prim(rcv, "rem:", |rcv, arg| rcv.rem(self, arg)),
+            Primitive::RemoveKey => {
+                let key = unsafe { &mut *self.stack.get() }.pop();
+                let dict: &Dictionary = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(stry!(dict.remove_key(self, key)));
+                SendReturn::Val
+            }
+            Primitive::RemoveFirst => {
+                let vec: &Vector = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(stry!(vec.remove_first()));
+                SendReturn::Val
+            }
+            Primitive::RemoveLast => {
+                let vec: &Vector = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(stry!(vec.remove_last()));
+                SendReturn::Val
+            }
+            Primitive::RemoveSelector => {
+                let sel = unsafe { &mut *self.stack.get() }.pop();
+                let cls: &Class = stry!(rcv.downcast(self));
+                let sel_str = stry!(String_::from_val(self, &sel));
+                let removed = cls.remove_method(self.intern_selector(sel_str.as_str()));
+                self.flush_inline_caches();
+                unsafe { &mut *self.stack.get() }.push(removed.unwrap_or_else(|| self.nil.clone()));
+                SendReturn::Val
+            }
+            Primitive::Restart => {
+                // `restart` is handled by `exec_user` when it is called directly from a method: it
+                // cannot be called any other way (e.g. via `perform:` or `VM::send`).
+                SendReturn::Err(Box::new(VMError::PrimitiveError))
+            }
+            Primitive::Round => {
+                // SOM rounds halves towards positive infinity (as Java's `Math.round` does), so
+                // `-2.5 round` is -2, whereas Rust's `f64::round` rounds halves away from zero.
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }
+                    .push(stry!(Double::f64_to_int(self, (d + 0.5).floor())));
+                SendReturn::Val
+            }
+            Primitive::Selectors => {
+                let cls: &Class = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(cls.selectors(self));
+                SendReturn::Val
+            }
+            Primitive::PositiveInfinity => {
+                unsafe { &mut *self.stack.get() }.push(Double::new(self, std::f64::INFINITY));
+                SendReturn::Val
+            }
             Primitive::PrintNewline => {
                 println!();
                 unsafe { &mut *self.stack.get() }.push(self.system.clone());
@@ -601,7 +1422,7 @@ impl VM {
             }
             Primitive::PrintString => {
                 let v = unsafe { &mut *self.stack.get() }.pop();
-                let str_: &String_ = stry!(v.downcast(self));
+                let str_ = stry!(String_::from_val(self, &v));
                 print!("{}", str_.as_str());
                 unsafe { &mut *self.stack.get() }.push(self.system.clone());
                 SendReturn::Val
@@ -611,13 +1432,83 @@ impl VM {
                     .push(stry!(rcv.shl(self, unsafe { &mut *self.stack.get() }.pop())));
                 SendReturn::Val
             }
+            Primitive::Shr => {
+                unsafe { &mut *self.stack.get() }
+                    .push(stry!(rcv.shr(self, unsafe { &mut *self.stack.get() }.pop())));
+                SendReturn::Val
+            }
+            Primitive::Signature => {
+                let meth: &Method = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(self.intern_symbol(meth.name.clone()));
+                SendReturn::Val
+            }
+            Primitive::Sin => {
+                let d = stry!(rcv.downcast::<Double>(self)).double();
+                unsafe { &mut *self.stack.get() }.push(Double::new(self, d.sin()));
+                SendReturn::Val
+            }
+            Primitive::Split => {
+                let str_ = stry!(String_::from_val(self, &rcv));
+                unsafe { &mut *self.stack.get() }.push(stry!(
+                    str_.split(self, unsafe { &mut *self.stack.get() }.pop())
+                ));
+                SendReturn::Val
+            }
+            Primitive::Sort => {
+                let cmp = unsafe { &mut *self.stack.get() }.pop();
+                if let Some(arr) = rcv.try_downcast::<Array>(self) {
+                    stry!(arr.sort(self, cmp));
+                } else {
+                    stry!(stry!(rcv.downcast::<Vector>(self)).sort(self, cmp));
+                }
+                unsafe { &mut *self.stack.get() }.push(rcv);
+                SendReturn::Val
+            }
             Primitive::Sqrt => {
                 unsafe { &mut *self.stack.get() }.push(stry!(rcv.sqrt(self)));
                 SendReturn::Val
             }
-            Primitive::Sub => {
+            Primitive::StringAsInteger => {
+                let str_ = stry!(String_::from_val(self, &rcv));
+                unsafe { &mut *self.stack.get() }.push(stry!(str_.to_integer(self)));
+                SendReturn::Val
+            }
+            Primitive::StringAt => {
+                let str_ = stry!(String_::from_val(self, &rcv));
                 unsafe { &mut *self.stack.get() }
-                    .push(stry!(rcv.sub(self, unsafe { &mut *self.stack.get() }.pop())));
+                    .push(stry!(str_.at(self, unsafe { &mut *self.stack.get() }.pop())));
+                SendReturn::Val
+            }
+            Primitive::StringLength => {
+                let str_ = stry!(String_::from_val(self, &rcv));
+                unsafe { &mut *self.stack.get() }.push(stry!(Val::from_usize(self, str_.length())));
+                SendReturn::Val
+            }
+            Primitive::Sub => self.exec_number_prim(rcv, "-", |rcv, arg| rcv.sub(self, arg)),
+            Primitive::Subclass => {
+                let fields = unsafe { &mut *self.stack.get() }.pop();
+                let name = unsafe { &mut *self.stack.get() }.pop();
+                let name_str = stry!(String_::from_val(self, &name));
+                let fields = stry!(fields.downcast::<Array>(self)).to_vec();
+                let mut field_names = Vec::with_capacity(fields.len());
+                for f in fields {
+                    field_names.push(stry!(String_::from_val(self, &f)).as_str().to_owned());
+                }
+                let subcls = stry!(Class::new_subclass(
+                    self,
+                    rcv,
+                    name_str.as_str(),
+                    field_names
+                ));
+                self.set_global(name_str.as_str(), subcls.clone());
+                unsafe { &mut *self.stack.get() }.push(subcls);
+                SendReturn::Val
+            }
+            Primitive::Substring => {
+                let end = unsafe { &mut *self.stack.get() }.pop();
+                let start = unsafe { &mut *self.stack.get() }.pop();
+                let str_ = stry!(String_::from_val(self, &rcv));
+                unsafe { &mut *self.stack.get() }.push(stry!(str_.substring(self, start, end)));
                 SendReturn::Val
             }
             Primitive::Superclass => {
@@ -625,26 +1516,90 @@ impl VM {
                 unsafe { &mut *self.stack.get() }.push(cls.superclass(self));
                 SendReturn::Val
             }
+            Primitive::SymbolNumArgs => {
+                let str_ = stry!(String_::from_val(self, &rcv));
+                unsafe { &mut *self.stack.get() }.push(stry!(Val::from_usize(
+                    self,
+                    Method::selector_num_args(str_.as_str())
+                )));
+                SendReturn::Val
+            }
+            Primitive::Ticks => {
+                let t = self.start_time.elapsed().as_micros();
+                unsafe { &mut *self.stack.get() }.push(stry!(Val::from_usize(self, t as usize)));
+                SendReturn::Val
+            }
+            Primitive::Time => {
+                let t = self.start_time.elapsed().as_millis();
+                unsafe { &mut *self.stack.get() }.push(stry!(Val::from_usize(self, t as usize)));
+                SendReturn::Val
+            }
+            Primitive::Values => {
+                let dict: &Dictionary = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(dict.values(self));
+                SendReturn::Val
+            }
+            Primitive::VectorAt => {
+                let vec: &Vector = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }
+                    .push(stry!(vec.at(self, unsafe { &mut *self.stack.get() }.pop())));
+                SendReturn::Val
+            }
+            Primitive::VectorAtPut => {
+                let val = unsafe { &mut *self.stack.get() }.pop();
+                let idx = unsafe { &mut *self.stack.get() }.pop();
+                let vec: &Vector = stry!(rcv.downcast(self));
+                stry!(vec.at_put(self, idx, val.clone()));
+                unsafe { &mut *self.stack.get() }.push(val);
+                SendReturn::Val
+            }
+            Primitive::VectorNew => {
+                unsafe { &mut *self.stack.get() }.push(Vector::new(self, rcv));
+                SendReturn::Val
+            }
+            Primitive::VectorSize => {
+                let vec: &Vector = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(stry!(Val::from_usize(self, vec.size())));
+                SendReturn::Val
+            }
+            Primitive::WriteStreamOn => {
+                let coll = unsafe { &mut *self.stack.get() }.pop();
+                unsafe { &mut *self.stack.get() }.push(stry!(WriteStream::new(self, coll)));
+                SendReturn::Val
+            }
+            Primitive::UpTo => {
+                let rs: &ReadStream = stry!(rcv.downcast(self));
+                unsafe { &mut *self.stack.get() }.push(stry!(
+                    rs.up_to(self, unsafe { &mut *self.stack.get() }.pop())
+                ));
+                SendReturn::Val
+            }
             Primitive::Value(nargs) => {
                 let rcv_blk: &Block = stry!(rcv.downcast(self));
-                let (num_vars, bytecode_off, max_stack) = {
+                let (num_vars, bytecode_off, max_stack, needs_closure) = {
                     let blkinfo = &unsafe { &*self.blockinfos.get() }[rcv_blk.blockinfo_off];
-                    (blkinfo.num_vars, blkinfo.bytecode_off, blkinfo.max_stack)
+                    (
+                        blkinfo.num_vars,
+                        blkinfo.bytecode_off,
+                        blkinfo.max_stack,
+                        blkinfo.needs_closure,
+                    )
                 };
-                if unsafe { &*self.stack.get() }.remaining_capacity() < max_stack {
-                    panic!("Not enough stack space to execute block.");
+                if unsafe { &*self.stack.get() }.remaining_capacity() < max_stack + num_vars {
+                    return SendReturn::Err(Box::new(VMError::StackOverflow));
                 }
                 let frame = Frame::new(
                     self,
                     false,
                     rcv.clone(),
-                    Some(Gc::clone(&rcv_blk.parent_closure)),
+                    rcv_blk.parent_closure.clone(),
                     num_vars,
                     nargs as usize,
+                    needs_closure,
                 );
                 unsafe { &mut *self.frames.get() }.push(frame);
                 let r = self.exec_user(rcv.clone(), bytecode_off);
-                self.frame_pop();
+                self.frame_pop(&r);
                 r
             }
         }
@@ -656,8 +1611,16 @@ impl VM {
         unsafe { (&*self.frames.get()).get_unchecked(frames_len - 1) }
     }
 
-    fn frame_pop(&self) {
-        unsafe { &mut *self.frames.get() }.pop();
+    /// Pop the current frame. If the frame stored its variables on the stack and `r` is a normal
+    /// return, the variables are removed from underneath the return value.
+    fn frame_pop(&self, r: &SendReturn) {
+        let frame = unsafe { &mut *self.frames.get() }.pop().unwrap();
+        if let (FrameVars::Stack { start, .. }, SendReturn::Val) = (&frame.vars, r) {
+            let stack = unsafe { &mut *self.stack.get() };
+            let v = stack.pop();
+            stack.truncate(*start);
+            stack.push(v);
+        }
     }
 
     /// Add `blkinfo` to the set of known `BlockInfo`s and return its index.
@@ -678,37 +1641,133 @@ impl VM {
     pub fn new_inline_cache(&self) -> usize {
         let ics = unsafe { &mut *self.inline_caches.get() };
         let len = ics.len();
-        ics.push(None);
+        ics.push(InlineCache::Polymorphic(Vec::new()));
         len
     }
 
-    /// Lookup the method `name` in the class `rcv_cls`, utilising the inline cache at index `idx`.
-    ///
-    /// # Guarantees for UnsafeCell
-    ///
-    /// This method guarantees not to mutate `self.sends`.
+    /// Empty all inline caches (including the global method cache). This must be called whenever
+    /// a class's methods are changed at run time.
+    fn flush_inline_caches(&self) {
+        for ic in unsafe { &mut *self.inline_caches.get() }.iter_mut() {
+            *ic = InlineCache::Polymorphic(Vec::new());
+        }
+        unsafe { &mut *self.method_cache.get() }.clear();
+        // A quickened send assumes that the method it replaces is an `Integer` primitive, which
+        // may no longer be true.
+        let instrs = unsafe { &mut *self.instrs.get() };
+        for pc in unsafe { &mut *self.quickened.get() }.drain() {
+            if let Instr::QuickSend(_, send_idx, cache_idx) = instrs[pc] {
+                instrs[pc] = Instr::Send(send_idx, cache_idx);
+            }
+        }
+    }
+
+    /// Rewrite the `Send` instruction at index `pc` into a quickened send performing `qop`.
+    fn quicken(&self, pc: usize, qop: QuickOp) {
+        let instrs = unsafe { &mut *self.instrs.get() };
+        if let Instr::Send(send_idx, cache_idx) = instrs[pc] {
+            instrs[pc] = Instr::QuickSend(qop, send_idx, cache_idx);
+            unsafe { &mut *self.quickened.get() }.insert(pc);
+        } else {
+            unreachable!();
+        }
+    }
+
+    /// Rewrite the quickened send at index `pc` back into a `Send` instruction.
+    fn deoptimise(&self, pc: usize) {
+        let instrs = unsafe { &mut *self.instrs.get() };
+        if let Instr::QuickSend(_, send_idx, cache_idx) = instrs[pc] {
+            instrs[pc] = Instr::Send(send_idx, cache_idx);
+            unsafe { &mut *self.quickened.get() }.remove(&pc);
+        } else {
+            unreachable!();
+        }
+    }
+
+    /// Lookup the method with selector `sel` in the class `rcv_cls`, utilising the inline cache
+    /// at index `idx`. If the send site has seen more than `MAX_PIC_ENTRIES` receiver classes, it
+    /// becomes megamorphic and all subsequent lookups at that site use only the global method
+    /// cache.
     pub fn inline_cache_lookup(
         &self,
         idx: usize,
+        sel: usize,
         rcv_cls: Val,
-        name: &str,
-    ) -> Result<Gc<Method>, Box<VMError>> {
+    ) -> Result<Val, Box<VMError>> {
+        let stats = unsafe { &mut *self.ic_stats.get() };
         // Lookup the method in the inline cache.
-        {
-            let cache = &unsafe { &*self.inline_caches.get() }[idx];
-            if let Some((cache_cls, cache_meth)) = cache {
-                if cache_cls.bit_eq(&rcv_cls) {
-                    return Ok(Gc::clone(cache_meth));
+        let full = match &unsafe { &*self.inline_caches.get() }[idx] {
+            InlineCache::Polymorphic(entries) => {
+                for (cache_cls, cache_meth) in entries {
+                    if cache_cls.bit_eq(&rcv_cls) {
+                        stats.hits += 1;
+                        return Ok(cache_meth.clone());
+                    }
                 }
+                entries.len() == MAX_PIC_ENTRIES
             }
+            InlineCache::Megamorphic => return self.lookup_method(rcv_cls, sel),
+        };
+        stats.misses += 1;
+        if full {
+            // There's no space left in the inline cache, so the send site is now megamorphic.
+            stats.megamorphic_sites += 1;
+            unsafe { &mut *self.inline_caches.get() }
+            [idx] = InlineCache::Megamorphic;
+            return self.lookup_method(rcv_cls, sel);
         }
-        // The inline cache is empty or out of date, so store a new value in it.
-        let meth = rcv_cls.downcast::<Class>(self)?.get_method(self, &name)?;
-        let ics = unsafe { &mut *self.inline_caches.get() };
-        ics[idx] = Some((rcv_cls, Gc::clone(&meth)));
+        let meth = self.lookup_method(rcv_cls.clone(), sel)?;
+        if let InlineCache::Polymorphic(entries) =
+            &mut unsafe { &mut *self.inline_caches.get() }[idx]
+        {
+            entries.push((rcv_cls, meth.clone()));
+        }
+        Ok(meth)
+    }
+
+    /// Lookup the method with selector `sel` in the class `cls` (and its superclasses), utilising
+    /// the global method cache.
+    pub fn lookup_method(&self, cls: Val, sel: usize) -> Result<Val, Box<VMError>> {
+        let stats = unsafe { &mut *self.ic_stats.get() };
+        let key = (cls.identity(), sel);
+        if let Some((_, meth)) = unsafe { &*self.method_cache.get() }.get(&key) {
+            stats.method_cache_hits += 1;
+            return Ok(meth.clone());
+        }
+        stats.method_cache_misses += 1;
+        let meth = cls.downcast::<Class>(self)?.get_method(self, sel)?;
+        unsafe { &mut *self.method_cache.get() }.insert(key, (cls, meth.clone()));
         Ok(meth)
     }
 
+    /// Return the ID of the selector `name`, interning it if necessary. IDs are small integers
+    /// which are shared by the compiler, classes, and the VM.
+    pub fn intern_selector(&self, name: &str) -> usize {
+        let reverse_selectors = unsafe { &mut *self.reverse_selectors.get() };
+        if let Some(i) = reverse_selectors.get(name) {
+            *i
+        } else {
+            let selectors = unsafe { &mut *self.selectors.get() };
+            let len = selectors.len();
+            reverse_selectors.insert(name.to_owned(), len);
+            selectors.push(name.to_owned());
+            len
+        }
+    }
+
+    /// Return the name of the selector with ID `sel`.
+    pub fn selector_name(&self, sel: usize) -> &str {
+        // Selectors are never removed, and a `String`'s contents do not move when the `Vec`
+        // holding it is resized, so the returned reference remains valid.
+        unsafe { &*self.selectors.get() }
+        [sel].as_str()
+    }
+
+    /// Return the counters recording how effective this VM's inline caches have been.
+    pub fn inline_cache_stats(&self) -> InlineCacheStats {
+        *unsafe { &*self.ic_stats.get() }
+    }
+
     /// How many instructions are currently present in the VM?
     pub fn instrs_len(&self) -> usize {
         unsafe { &*self.instrs.get() }.len()
@@ -721,19 +1780,14 @@ impl VM {
 
     /// Add the send `send` to the VM, returning its index. Note that sends are reused, so indexes
     /// are also reused.
-    pub fn add_send(&self, send: (String, usize)) -> usize {
-        let reverse_sends = unsafe { &mut *self.reverse_sends.get() };
-        // We want to avoid `clone`ing `send` in the (hopefully common) case of a cache hit, hence
-        // this slightly laborious dance and double-lookup.
-        if let Some(i) = reverse_sends.get(&send) {
-            *i
-        } else {
-            let sends = unsafe { &mut *self.sends.get() };
-            let len = sends.len();
-            reverse_sends.insert(send.clone(), len);
-            sends.push(send);
-            len
-        }
+    pub fn add_send(&self, send: (usize, usize)) -> usize {
+        let sends = unsafe { &mut *self.sends.get() };
+        *unsafe { &mut *self.reverse_sends.get() }
+            .entry(send)
+            .or_insert_with(|| {
+                sends.push(send);
+                sends.len() - 1
+            })
     }
 
     /// Add the string `s` to the VM, returning its index. Note that strings are reused, so indexes
@@ -748,7 +1802,7 @@ impl VM {
             let strings = unsafe { &mut *self.strings.get() };
             let len = strings.len();
             reverse_strings.insert(s.clone(), len);
-            strings.push(String_::new(self, s, true));
+            strings.push(String_::new(self, s));
             len
         }
     }
@@ -765,10 +1819,46 @@ impl VM {
             let symbols = unsafe { &mut *self.symbols.get() };
             let len = symbols.len();
             reverse_symbols.insert(s.clone(), len);
-            symbols.push(String_::new(self, s, false));
+            symbols.push(Symbol::new(self, s));
             len
         }
     }
+
+    /// Return the index of the cell for the global `name`, creating an (unassigned) cell if it
+    /// does not already exist. Note that cells are reused, so indexes are also reused.
+    pub fn add_global(&self, name: &str) -> usize {
+        let reverse_globals = unsafe { &mut *self.reverse_globals.get() };
+        if let Some(i) = reverse_globals.get(name) {
+            *i
+        } else {
+            let globals = unsafe { &mut *self.globals.get() };
+            let len = globals.len();
+            reverse_globals.insert(name.to_owned(), len);
+            globals.push(None);
+            len
+        }
+    }
+
+    /// Return the value of the global `name`, or `None` if it has not been assigned.
+    pub fn get_global(&self, name: &str) -> Option<Val> {
+        let i = *unsafe { &*self.reverse_globals.get() }.get(name)?;
+        unsafe { &*self.globals.get() }
+        [i].clone()
+    }
+
+    /// Set the global `name` to `val`.
+    pub fn set_global(&self, name: &str, val: Val) {
+        let i = self.add_global(name);
+        unsafe { &mut *self.globals.get() }
+        [i] = Some(val);
+    }
+
+    /// Return the symbol `s`, creating it if it does not already exist.
+    pub fn intern_symbol(&self, s: String) -> Val {
+        let i = self.add_symbol(s);
+        unsafe { &*self.symbols.get() }
+        [i].clone()
+    }
 }
 
 #[derive(Debug)]
@@ -776,10 +1866,28 @@ pub struct Frame {
     /// Stack pointer. Note that this is updated lazily (i.e. it might not be accurate at all
     /// points, but it is guaranteed to be correct over function calls).
     sp: UnsafeCell<usize>,
-    closure: Gc<Closure>,
+    vars: FrameVars,
+}
+
+/// Where a frame's variables are stored.
+#[derive(Debug)]
+enum FrameVars {
+    /// The frame's variables are captured by at least one block, so they are stored in a heap
+    /// allocated closure.
+    Closure(Gc<Closure>),
+    /// The frame's variables are not captured by any block, so they are stored on the SOM stack
+    /// starting at index `start`. `parent` is the closure of the enclosing method or block (if
+    /// there is one).
+    Stack {
+        start: usize,
+        parent: Option<Gc<Closure>>,
+    },
 }
 
 impl Frame {
+    /// Create a new frame, popping its `num_args` arguments off the stack. If `needs_closure` is
+    /// true, the frame's variables are stored in a new closure; otherwise they are stored on the
+    /// stack, and the caller must have checked that the stack has room for `num_vars` more values.
     fn new(
         vm: &VM,
         is_method: bool,
@@ -787,21 +1895,44 @@ impl Frame {
         parent_closure: Option<Gc<Closure>>,
         num_vars: usize,
         num_args: usize,
+        needs_closure: bool,
     ) -> Self {
+        let stack = unsafe { &mut *vm.stack.get() };
+        if !needs_closure {
+            // The arguments are already in the right place on the stack: for methods, we need
+            // only insert `self` before them.
+            let start = stack.len() - num_args;
+            let mut num_set = num_args;
+            if is_method {
+                stack.insert(start, self_val);
+                num_set += 1;
+            }
+            for _ in num_set..num_vars {
+                stack.push(vm.nil.clone());
+            }
+            return Frame {
+                sp: UnsafeCell::new(0),
+                vars: FrameVars::Stack {
+                    start,
+                    parent: parent_closure,
+                },
+            };
+        }
+
         let mut vars = Vec::with_capacity(num_vars);
         vars.resize_with(num_vars, || Val::illegal());
 
         if is_method {
             vars[0] = self_val;
             for i in 0..num_args {
-                vars[num_args - i] = unsafe { &mut *vm.stack.get() }.pop();
+                vars[num_args - i] = stack.pop();
             }
             for v in vars.iter_mut().skip(num_args + 1).take(num_vars) {
                 *v = vm.nil.clone();
             }
         } else {
             for i in 0..num_args {
-                vars[num_args - i - 1] = unsafe { &mut *vm.stack.get() }.pop();
+                vars[num_args - i - 1] = stack.pop();
             }
             for v in vars.iter_mut().skip(num_args).take(num_vars) {
                 *v = vm.nil.clone();
@@ -810,22 +1941,50 @@ impl Frame {
 
         Frame {
             sp: UnsafeCell::new(0),
-            closure: Gc::new(Closure::new(parent_closure, vars)),
+            vars: FrameVars::Closure(Gc::new(Closure::new(parent_closure, vars))),
         }
     }
 
-    fn var_lookup(&self, depth: usize, var: usize) -> Val {
+    fn var_lookup(&self, vm: &VM, depth: usize, var: usize) -> Val {
+        if depth == 0 {
+            if let FrameVars::Stack { start, .. } = self.vars {
+                return unsafe { &*vm.stack.get() }.get(start + var);
+            }
+        }
         self.closure(depth).get_var(var)
     }
 
-    fn var_set(&self, depth: usize, var: usize, val: Val) {
+    fn var_set(&self, vm: &VM, depth: usize, var: usize, val: Val) {
+        if depth == 0 {
+            if let FrameVars::Stack { start, .. } = self.vars {
+                unsafe { &mut *vm.stack.get() }.set(start + var, val);
+                return;
+            }
+        }
         self.closure(depth).set_var(var, val);
     }
 
+    /// Return this frame's own closure, or `None` if this frame's variables are stored on the
+    /// stack.
+    fn own_closure(&self) -> Option<Gc<Closure>> {
+        match &self.vars {
+            FrameVars::Closure(c) => Some(Gc::clone(c)),
+            FrameVars::Stack { .. } => None,
+        }
+    }
+
     /// Return the closure `depth` closures up from this frame's closure (where `depth` can be 0
-    /// which returns this frame's closure).
+    /// which returns this frame's closure). If this frame's variables are stored on the stack,
+    /// `depth` must be greater than 0.
     fn closure(&self, mut depth: usize) -> Gc<Closure> {
-        let mut c = Gc::clone(&self.closure);
+        let mut c = match &self.vars {
+            FrameVars::Closure(c) => Gc::clone(c),
+            FrameVars::Stack { parent, .. } => {
+                debug_assert!(depth > 0);
+                depth -= 1;
+                Gc::clone(parent.as_ref().unwrap())
+            }
+        };
         while depth > 0 {
             c = Gc::clone(c.parent.as_ref().unwrap());
             depth -= 1;
@@ -887,36 +2046,52 @@ impl VM {
     pub fn new_no_bootstrap() -> Self {
         VM {
             classpath: vec![],
+            opts: VMOptions::default(),
+            array_cls: Val::illegal(),
             block_cls: Val::illegal(),
             block2_cls: Val::illegal(),
             block3_cls: Val::illegal(),
             bool_cls: Val::illegal(),
             cls_cls: Val::illegal(),
+            dict_cls: Val::illegal(),
             double_cls: Val::illegal(),
             false_cls: Val::illegal(),
+            fraction_cls: Val::illegal(),
             int_cls: Val::illegal(),
+            method_cls: Val::illegal(),
             obj_cls: Val::illegal(),
             nil_cls: Val::illegal(),
+            primitive_cls: Val::illegal(),
+            read_stream_cls: Val::illegal(),
             str_cls: Val::illegal(),
             sym_cls: Val::illegal(),
             system_cls: Val::illegal(),
             true_cls: Val::illegal(),
+            vector_cls: Val::illegal(),
+            write_stream_cls: Val::illegal(),
             false_: Val::illegal(),
             nil: Val::illegal(),
             system: Val::illegal(),
             true_: Val::illegal(),
             blockinfos: UnsafeCell::new(Vec::new()),
-            globals: UnsafeCell::new(HashMap::new()),
+            globals: UnsafeCell::new(Vec::new()),
+            reverse_globals: UnsafeCell::new(HashMap::new()),
             inline_caches: UnsafeCell::new(Vec::new()),
+            method_cache: UnsafeCell::new(HashMap::new()),
+            ic_stats: UnsafeCell::new(InlineCacheStats::default()),
+            quickened: UnsafeCell::new(HashSet::new()),
             instrs: UnsafeCell::new(Vec::new()),
             sends: UnsafeCell::new(Vec::new()),
             reverse_sends: UnsafeCell::new(HashMap::new()),
+            selectors: UnsafeCell::new(Vec::new()),
+            reverse_selectors: UnsafeCell::new(HashMap::new()),
             stack: UnsafeCell::new(SOMStack::new()),
             strings: UnsafeCell::new(Vec::new()),
             reverse_strings: UnsafeCell::new(HashMap::new()),
             symbols: UnsafeCell::new(Vec::new()),
             reverse_symbols: UnsafeCell::new(HashMap::new()),
             frames: UnsafeCell::new(Vec::new()),
+            start_time: Instant::now(),
         }
     }
 }
@@ -931,9 +2106,20 @@ mod tests {
         let selfv = Val::from_isize(&vm, 42).unwrap();
         unsafe { &mut *vm.stack.get() }.push(Val::from_isize(&vm, 43).unwrap());
         unsafe { &mut *vm.stack.get() }.push(Val::from_isize(&vm, 44).unwrap());
-        let f = Frame::new(&vm, true, selfv, None, 3, 2);
-        assert_eq!(f.var_lookup(0, 0).as_isize(&vm).unwrap(), 42);
-        assert_eq!(f.var_lookup(0, 1).as_isize(&vm).unwrap(), 43);
-        assert_eq!(f.var_lookup(0, 2).as_isize(&vm).unwrap(), 44);
+        let f = Frame::new(&vm, true, selfv.clone(), None, 3, 2, true);
+        assert_eq!(f.var_lookup(&vm, 0, 0).as_isize(&vm).unwrap(), 42);
+        assert_eq!(f.var_lookup(&vm, 0, 1).as_isize(&vm).unwrap(), 43);
+        assert_eq!(f.var_lookup(&vm, 0, 2).as_isize(&vm).unwrap(), 44);
+        assert!(unsafe { &*vm.stack.get() }.is_empty());
+
+        unsafe { &mut *vm.stack.get() }.push(Val::from_isize(&vm, 43).unwrap());
+        unsafe { &mut *vm.stack.get() }.push(Val::from_isize(&vm, 44).unwrap());
+        let f = Frame::new(&vm, true, selfv, None, 4, 2, false);
+        assert_eq!(f.var_lookup(&vm, 0, 0).as_isize(&vm).unwrap(), 42);
+        assert_eq!(f.var_lookup(&vm, 0, 1).as_isize(&vm).unwrap(), 43);
+        assert_eq!(f.var_lookup(&vm, 0, 2).as_isize(&vm).unwrap(), 44);
+        assert_eq!(unsafe { &*vm.stack.get() }.len(), 4);
+        f.var_set(&vm, 0, 3, Val::from_isize(&vm, 45).unwrap());
+        assert_eq!(f.var_lookup(&vm, 0, 3).as_isize(&vm).unwrap(), 45);
     }
 }
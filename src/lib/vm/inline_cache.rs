@@ -0,0 +1,49 @@
+//! Inline caches for send sites. Each send site has its own polymorphic inline cache (PIC) which
+//! remembers the method looked up for each of the first few receiver classes seen at that site.
+//! Sites which see more receiver classes than a PIC can hold are "megamorphic": lookups at such
+//! sites go straight to the VM's global method cache.
+
+use std::fmt;
+
+use crate::vm::val::Val;
+
+/// The maximum number of receiver classes a send site's PIC can hold before the site is considered
+/// megamorphic.
+pub const MAX_PIC_ENTRIES: usize = 4;
+
+/// The inline cache for a single send site.
+#[derive(Debug)]
+pub enum InlineCache {
+    /// The `(class, method)` pairs for each receiver class seen at this send site so far. This
+    /// never contains more than `MAX_PIC_ENTRIES` pairs.
+    Polymorphic(Vec<(Val, Val)>),
+    /// This send site has seen more than `MAX_PIC_ENTRIES` receiver classes: lookups must use the
+    /// VM's global method cache.
+    Megamorphic,
+}
+
+/// Counters recording how effective the VM's inline caches have been.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InlineCacheStats {
+    /// Lookups which were found in a send site's PIC.
+    pub hits: u64,
+    /// Lookups which were not found in a send site's PIC.
+    pub misses: u64,
+    /// How many send sites have become megamorphic.
+    pub megamorphic_sites: u64,
+    /// Lookups which were found in the global method cache.
+    pub method_cache_hits: u64,
+    /// Lookups which were not found in the global method cache, and thus had to search a class
+    /// and its superclasses.
+    pub method_cache_misses: u64,
+}
+
+impl fmt::Display for InlineCacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Inline cache hits: {}", self.hits)?;
+        writeln!(f, "Inline cache misses: {}", self.misses)?;
+        writeln!(f, "Megamorphic send sites: {}", self.megamorphic_sites)?;
+        writeln!(f, "Method cache hits: {}", self.method_cache_hits)?;
+        writeln!(f, "Method cache misses: {}", self.method_cache_misses)
+    }
+}
@@ -9,8 +9,9 @@
 //! [`Val::try_downcast`](vm::val::Val::try_downcast)) it to a concrete implementation of `Obj`.
 
 pub mod core;
+pub mod inline_cache;
 pub mod objects;
 pub mod somstack;
 pub mod val;
 
-pub use crate::vm::core::{VMError, VM};
+pub use crate::vm::core::{VMError, VMOptions, VM};
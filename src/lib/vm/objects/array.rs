@@ -0,0 +1,134 @@
+#![allow(clippy::new_ret_no_self)]
+
+use std::{
+    cell::UnsafeCell,
+    mem::{self, size_of, size_of_val},
+};
+
+use abgc_derive::GcLayout;
+
+use crate::vm::{
+    core::{VMError, VM},
+    objects::{Obj, ObjType, StaticObjType},
+    val::{NotUnboxable, Val},
+};
+
+/// A fixed-size array of SOM values.
+#[derive(Debug, GcLayout)]
+pub struct Array {
+    store: UnsafeCell<Vec<Val>>,
+}
+
+impl Obj for Array {
+    fn dyn_objtype(&self) -> ObjType {
+        ObjType::Array
+    }
+
+    fn get_class(&self, vm: &VM) -> Val {
+        vm.array_cls.clone()
+    }
+
+    fn object_size(&self) -> usize {
+        size_of_val(self) + self.length() * size_of::<Val>()
+    }
+}
+
+impl NotUnboxable for Array {}
+
+impl StaticObjType for Array {
+    fn static_objtype() -> ObjType {
+        ObjType::Array
+    }
+}
+
+impl Array {
+    /// Create a new array of `len` elements, each of which is initialised to `nil`.
+    pub fn new(vm: &VM, len: usize) -> Val {
+        let mut store = Vec::with_capacity(len);
+        store.resize(len, vm.nil.clone());
+        Array::from_vec(vm, store)
+    }
+
+    /// Create a new array whose elements are `store`.
+    pub fn from_vec(vm: &VM, store: Vec<Val>) -> Val {
+        Val::from_obj(
+            vm,
+            Array {
+                store: UnsafeCell::new(store),
+            },
+        )
+    }
+
+    /// How many elements does this array have?
+    pub fn length(&self) -> usize {
+        unsafe { &*self.store.get() }.len()
+    }
+
+    /// Return the element at SOM index `idx`.
+    pub fn at(&self, vm: &VM, idx: Val) -> Result<Val, Box<VMError>> {
+        let store = unsafe { &*self.store.get() };
+        Ok(store[idx.as_index(vm, store.len())?].clone())
+    }
+
+    /// Return the element at the 0-based index `i`, or `None` if `i` is out of bounds.
+    pub fn get(&self, i: usize) -> Option<Val> {
+        unsafe { &*self.store.get() }.get(i).cloned()
+    }
+
+    /// Set the element at SOM index `idx` to `val`.
+    pub fn at_put(&self, vm: &VM, idx: Val, val: Val) -> Result<(), Box<VMError>> {
+        let store = unsafe { &mut *self.store.get() };
+        let i = idx.as_index(vm, store.len())?;
+        store[i] = val;
+        Ok(())
+    }
+
+    /// Return a copy of this array's elements.
+    pub fn to_vec(&self) -> Vec<Val> {
+        unsafe { &*self.store.get() }.clone()
+    }
+
+    /// Sort this array in place using the comparator block `cmp`, which is sent `value:with:` with
+    /// two elements and should return `true` if the first should be ordered before the second.
+    /// The sort is stable.
+    pub fn sort(&self, vm: &VM, cmp: Val) -> Result<(), Box<VMError>> {
+        // The comparator can run arbitrary SOM code (which might even mutate this array), so we
+        // sort a copy of the elements and only update the array once the sort has succeeded.
+        let mut vals = self.to_vec();
+        Array::sort_vals(vm, &mut vals, &cmp)?;
+        *unsafe { &mut *self.store.get() } = vals;
+        Ok(())
+    }
+
+    /// Merge sort `vals` using the comparator block `cmp`: see `Array::sort`.
+    pub fn sort_vals(vm: &VM, vals: &mut Vec<Val>, cmp: &Val) -> Result<(), Box<VMError>> {
+        if vals.len() <= 1 {
+            return Ok(());
+        }
+        let mut rhs = vals.split_off(vals.len() / 2);
+        Array::sort_vals(vm, vals, cmp)?;
+        Array::sort_vals(vm, &mut rhs, cmp)?;
+        let len = vals.len() + rhs.len();
+        let lhs = mem::replace(vals, Vec::with_capacity(len));
+        let (mut i, mut j) = (0, 0);
+        while i < lhs.len() && j < rhs.len() {
+            // To keep the sort stable, we only take from `rhs` if its element is strictly ordered
+            // before `lhs`'s.
+            let r = vm.send(
+                cmp.clone(),
+                "value:with:",
+                vec![rhs[j].clone(), lhs[i].clone()],
+            )?;
+            if r.bit_eq(&vm.true_) {
+                vals.push(rhs[j].clone());
+                j += 1;
+            } else {
+                vals.push(lhs[i].clone());
+                i += 1;
+            }
+        }
+        vals.extend_from_slice(&lhs[i..]);
+        vals.extend_from_slice(&rhs[j..]);
+        Ok(())
+    }
+}
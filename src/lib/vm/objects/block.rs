@@ -17,6 +17,8 @@ pub struct BlockInfo {
     pub num_params: usize,
     pub num_vars: usize,
     pub max_stack: usize,
+    /// Are any of this block's variables captured by a nested block?
+    pub needs_closure: bool,
 }
 
 #[derive(Debug, GcLayout)]
@@ -24,7 +26,9 @@ pub struct Block {
     // Does this Block represent Block, Block2, or Block3?
     pub blockn_cls: Val,
     pub blockinfo_off: usize,
-    pub parent_closure: Gc<Closure>,
+    /// The closure of the method or block which created this block, or `None` if that method or
+    /// block's variables are not captured (in which case this block never needs to access them).
+    pub parent_closure: Option<Gc<Closure>>,
 }
 
 impl Obj for Block {
@@ -49,14 +53,15 @@ impl Block {
     pub fn new(
         vm: &VM,
         blockinfo_off: usize,
-        parent_closure: Gc<Closure>,
+        parent_closure: Option<Gc<Closure>>,
         num_params: usize,
     ) -> Val {
         let blockn_cls = match num_params {
             0 => vm.block_cls.clone(),
             1 => vm.block2_cls.clone(),
             2 => vm.block3_cls.clone(),
-            _ => unimplemented!(),
+            // The compiler rejects blocks with more than 2 parameters.
+            _ => unreachable!(),
         };
         Val::from_obj(
             vm,
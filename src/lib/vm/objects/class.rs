@@ -1,13 +1,12 @@
 #![allow(clippy::new_ret_no_self)]
 
-use std::{collections::HashMap, path::PathBuf, str};
+use std::{cell::UnsafeCell, collections::HashMap, path::PathBuf, str};
 
-use abgc::Gc;
 use abgc_derive::GcLayout;
 
 use crate::vm::{
     core::{VMError, VM},
-    objects::{Method, Obj, ObjType, StaticObjType},
+    objects::{Array, Method, Obj, ObjType, StaticObjType, String_},
     val::{NotUnboxable, Val},
 };
 
@@ -16,8 +15,15 @@ pub struct Class {
     pub name: Val,
     pub path: PathBuf,
     pub supercls: Option<Val>,
-    pub num_inst_vars: usize,
-    pub methods: HashMap<String, Gc<Method>>,
+    /// This class's metaclass (which stores its class methods). Classes without a metaclass are
+    /// direct instances of `Class`.
+    pub metacls: Option<Val>,
+    /// The names of this class's instance variables, in the order they are stored in an `Inst`.
+    pub inst_vars: Vec<String>,
+    /// This class's methods, each of which is a `Method`, keyed by their selector (as interned by
+    /// `VM::intern_selector`). Methods can be added and removed at run time, in which case the
+    /// VM's inline caches must be flushed.
+    pub methods: UnsafeCell<HashMap<usize, Val>>,
 }
 
 impl Obj for Class {
@@ -26,7 +32,10 @@ impl Obj for Class {
     }
 
     fn get_class(&self, vm: &VM) -> Val {
-        vm.cls_cls.clone()
+        match &self.metacls {
+            Some(metacls) => metacls.clone(),
+            None => vm.cls_cls.clone(),
+        }
     }
 }
 
@@ -39,20 +48,135 @@ impl StaticObjType for Class {
 }
 
 impl Class {
+    /// Create a new subclass of `supercls` (which must be a `Class`) called `name`, which has the
+    /// instance variables of `supercls` followed by `inst_vars`. The new class always has its own
+    /// metaclass, so that class methods can be added to it without affecting other classes.
+    pub fn new_subclass(
+        vm: &VM,
+        supercls: Val,
+        name: &str,
+        inst_vars: Vec<String>,
+    ) -> Result<Val, Box<VMError>> {
+        let scls: &Class = supercls.downcast(vm)?;
+        let mut all_inst_vars = scls.inst_vars.clone();
+        all_inst_vars.extend(inst_vars);
+        let metacls = Val::from_obj(
+            vm,
+            Class {
+                name: String_::new(vm, format!("{} class", name)),
+                path: scls.path.clone(),
+                supercls: Some(supercls.get_class(vm)),
+                metacls: None,
+                inst_vars: vec![],
+                methods: UnsafeCell::new(HashMap::new()),
+            },
+        );
+        Ok(Val::from_obj(
+            vm,
+            Class {
+                name: String_::new(vm, name.to_owned()),
+                path: scls.path.clone(),
+                supercls: Some(supercls.clone()),
+                metacls: Some(metacls),
+                inst_vars: all_inst_vars,
+                methods: UnsafeCell::new(HashMap::new()),
+            },
+        ))
+    }
+
     pub fn name(&self, _: &VM) -> Result<Val, Box<VMError>> {
         Ok(self.name.clone())
     }
 
-    pub fn get_method(&self, vm: &VM, msg: &str) -> Result<Gc<Method>, Box<VMError>> {
-        self.methods
-            .get(msg)
-            .map(|x| Ok(Gc::clone(x)))
+    /// Set the holder of each of this class's methods to `cls`, which must be a `Val`
+    /// referencing `self`.
+    pub fn bind_methods(&self, vm: &VM, cls: &Val) {
+        for meth in unsafe { &*self.methods.get() }.values() {
+            meth.downcast::<Method>(vm).unwrap().set_holder(cls.clone());
+        }
+    }
+
+    /// Lookup the method with selector `sel` in this class and its superclasses, returning a
+    /// `Method`. Note that most callers should use `VM::lookup_method`, which caches the results
+    /// of lookups.
+    pub fn get_method(&self, vm: &VM, sel: usize) -> Result<Val, Box<VMError>> {
+        unsafe { &*self.methods.get() }
+            .get(&sel)
+            .map(|x| Ok(x.clone()))
             .unwrap_or_else(|| match &self.supercls {
-                Some(scls) => scls.downcast::<Class>(vm)?.get_method(vm, msg),
-                None => Err(Box::new(VMError::UnknownMethod(msg.to_owned()))),
+                Some(scls) => scls.downcast::<Class>(vm)?.get_method(vm, sel),
+                None => Err(Box::new(VMError::UnknownMethod(
+                    vm.selector_name(sel).to_owned(),
+                ))),
             })
     }
 
+    /// Return the selectors of the methods defined in this class (but not its superclasses),
+    /// sorted by name.
+    fn sorted_selectors(&self, vm: &VM) -> Vec<usize> {
+        let mut sels = unsafe { &*self.methods.get() }
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        sels.sort_by(|x, y| vm.selector_name(*x).cmp(vm.selector_name(*y)));
+        sels
+    }
+
+    /// Return an `Array` of the methods defined in this class (but not its superclasses), sorted
+    /// by name.
+    pub fn methods(&self, vm: &VM) -> Val {
+        let methods = unsafe { &*self.methods.get() };
+        Array::from_vec(
+            vm,
+            self.sorted_selectors(vm)
+                .iter()
+                .map(|sel| methods[sel].clone())
+                .collect(),
+        )
+    }
+
+    /// Return an `Array` of the selectors (as `Symbol`s) of the methods defined in this class (but
+    /// not its superclasses), sorted by name.
+    pub fn selectors(&self, vm: &VM) -> Val {
+        Array::from_vec(
+            vm,
+            self.sorted_selectors(vm)
+                .iter()
+                .map(|sel| vm.intern_symbol(vm.selector_name(*sel).to_owned()))
+                .collect(),
+        )
+    }
+
+    /// Return an `Array` of the names (as `Symbol`s) of this class's instance variables, in the
+    /// order they are stored in an `Inst`.
+    pub fn fields(&self, vm: &VM) -> Val {
+        Array::from_vec(
+            vm,
+            self.inst_vars
+                .iter()
+                .map(|n| vm.intern_symbol(n.to_owned()))
+                .collect(),
+        )
+    }
+
+    /// Add the method `meth` (which must be a `Method`) to this class, replacing any existing
+    /// method of the same name. `cls` must be a `Val` referencing `self`. Note that the caller is
+    /// responsible for flushing the VM's inline caches.
+    pub fn add_method(&self, vm: &VM, cls: &Val, meth: Val) -> Result<(), Box<VMError>> {
+        let meth_obj: &Method = meth.downcast(vm)?;
+        meth_obj.set_holder(cls.clone());
+        let sel = vm.intern_selector(&meth_obj.name);
+        unsafe { &mut *self.methods.get() }.insert(sel, meth);
+        Ok(())
+    }
+
+    /// Remove the method with selector `sel` from this class (but not its superclasses), returning
+    /// the removed `Method` if it existed. Note that the caller is responsible for flushing the
+    /// VM's inline caches.
+    pub fn remove_method(&self, sel: usize) -> Option<Val> {
+        unsafe { &mut *self.methods.get() }.remove(&sel)
+    }
+
     pub fn superclass(&self, vm: &VM) -> Val {
         if let Some(superclass) = &self.supercls {
             return superclass.clone();
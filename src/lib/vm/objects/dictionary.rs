@@ -0,0 +1,164 @@
+#![allow(clippy::new_ret_no_self)]
+
+use std::{
+    cell::UnsafeCell,
+    collections::HashMap,
+    mem::{size_of, size_of_val},
+};
+
+use abgc_derive::GcLayout;
+
+use crate::vm::{
+    core::{VMError, VM},
+    objects::{Array, Obj, ObjType, StaticObjType},
+    val::{NotUnboxable, Val},
+};
+
+/// A hash table mapping SOM keys to SOM values. Keys are compared using the SOM `=` and
+/// `hashcode` methods, so user classes which override them work as expected.
+#[derive(Debug, GcLayout)]
+pub struct Dictionary {
+    /// The class of this dictionary: either `Dictionary` or a subclass of it.
+    class: Val,
+    /// The dictionary's `(hash, key, value)` entries, in insertion order (though removing a key
+    /// moves the most recently inserted entry into the removed entry's position).
+    entries: UnsafeCell<Vec<(u64, Val, Val)>>,
+    /// Maps a hash to the indices in `entries` of all keys with that hash.
+    index: UnsafeCell<HashMap<u64, Vec<usize>>>,
+}
+
+impl Obj for Dictionary {
+    fn dyn_objtype(&self) -> ObjType {
+        ObjType::Dictionary
+    }
+
+    fn get_class(&self, _: &VM) -> Val {
+        self.class.clone()
+    }
+
+    fn object_size(&self) -> usize {
+        size_of_val(self) + self.size() * (size_of::<(u64, Val, Val)>() + size_of::<usize>())
+    }
+}
+
+impl NotUnboxable for Dictionary {}
+
+impl StaticObjType for Dictionary {
+    fn static_objtype() -> ObjType {
+        ObjType::Dictionary
+    }
+}
+
+impl Dictionary {
+    /// Create a new, empty, dictionary which is an instance of `class`.
+    pub fn new(vm: &VM, class: Val) -> Val {
+        Val::from_obj(
+            vm,
+            Dictionary {
+                class,
+                entries: UnsafeCell::new(Vec::new()),
+                index: UnsafeCell::new(HashMap::new()),
+            },
+        )
+    }
+
+    /// How many entries does this dictionary have?
+    pub fn size(&self) -> usize {
+        unsafe { &*self.entries.get() }.len()
+    }
+
+    /// Return the value associated with `key` or `nil` if there is no such value.
+    pub fn at(&self, vm: &VM, key: Val) -> Result<Val, Box<VMError>> {
+        match self.find(vm, &key)? {
+            (_, Some(i)) => Ok((unsafe { &*self.entries.get() })[i].2.clone()),
+            (_, None) => Ok(vm.nil.clone()),
+        }
+    }
+
+    /// Associate `val` with `key`, replacing any existing value.
+    pub fn at_put(&self, vm: &VM, key: Val, val: Val) -> Result<(), Box<VMError>> {
+        let (hash, i) = self.find(vm, &key)?;
+        let entries = unsafe { &mut *self.entries.get() };
+        match i {
+            Some(i) => entries[i].2 = val,
+            None => {
+                unsafe { &mut *self.index.get() }
+                    .entry(hash)
+                    .or_insert_with(Vec::new)
+                    .push(entries.len());
+                entries.push((hash, key, val));
+            }
+        }
+        Ok(())
+    }
+
+    /// Does this dictionary contain `key`?
+    pub fn contains_key(&self, vm: &VM, key: Val) -> Result<bool, Box<VMError>> {
+        Ok(self.find(vm, &key)?.1.is_some())
+    }
+
+    /// Remove `key` from this dictionary, returning its associated value, or `nil` if `key` was
+    /// not present.
+    pub fn remove_key(&self, vm: &VM, key: Val) -> Result<Val, Box<VMError>> {
+        let (hash, i) = match self.find(vm, &key)? {
+            (hash, Some(i)) => (hash, i),
+            (_, None) => return Ok(vm.nil.clone()),
+        };
+        let entries = unsafe { &mut *self.entries.get() };
+        let index = unsafe { &mut *self.index.get() };
+        let bucket = index.get_mut(&hash).unwrap();
+        bucket.retain(|x| *x != i);
+        if bucket.is_empty() {
+            index.remove(&hash);
+        }
+        let last = entries.len() - 1;
+        if i != last {
+            // The last entry is about to be moved into position `i`.
+            let last_bucket = index.get_mut(&entries[last].0).unwrap();
+            *last_bucket.iter_mut().find(|x| **x == last).unwrap() = i;
+        }
+        Ok(entries.swap_remove(i).2)
+    }
+
+    /// Return an `Array` of this dictionary's keys.
+    pub fn keys(&self, vm: &VM) -> Val {
+        let entries = unsafe { &*self.entries.get() };
+        Array::from_vec(vm, entries.iter().map(|(_, k, _)| k.clone()).collect())
+    }
+
+    /// Return an `Array` of this dictionary's values, in the same order as `keys`.
+    pub fn values(&self, vm: &VM) -> Val {
+        let entries = unsafe { &*self.entries.get() };
+        Array::from_vec(vm, entries.iter().map(|(_, _, v)| v.clone()).collect())
+    }
+
+    /// Return `key`'s hash and, if `key` is present, the index of its entry in `entries`. Since
+    /// this sends `hashcode` and `=` to `key`, which may run arbitrary SOM code, no references to
+    /// `entries` or `index` are held across sends.
+    fn find(&self, vm: &VM, key: &Val) -> Result<(u64, Option<usize>), Box<VMError>> {
+        let hash = vm.send(key.clone(), "hashcode", vec![])?;
+        let hash = match hash.as_isize(vm) {
+            Some(h) => h as u64,
+            None => {
+                return Err(Box::new(VMError::TypeError {
+                    expected: ObjType::Int,
+                    got: hash.dyn_objtype(vm),
+                }))
+            }
+        };
+        let candidates = match unsafe { &*self.index.get() }.get(&hash) {
+            Some(c) => c.clone(),
+            None => return Ok((hash, None)),
+        };
+        for i in candidates {
+            let other = match unsafe { &*self.entries.get() }.get(i) {
+                Some((_, k, _)) => k.clone(),
+                None => continue,
+            };
+            if vm.send(key.clone(), "=", vec![other])?.bit_eq(&vm.true_) {
+                return Ok((hash, Some(i)));
+            }
+        }
+        Ok((hash, None))
+    }
+}
@@ -1,7 +1,13 @@
 #![allow(clippy::new_ret_no_self)]
 
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
 use abgc_derive::GcLayout;
-use num_traits::{ToPrimitive, Zero};
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, ToPrimitive, Zero};
 
 use crate::vm::{
     core::{VMError, VM},
@@ -26,9 +32,26 @@ impl Obj for Double {
         vm.double_cls.clone()
     }
 
+    fn hashcode(&self) -> u64 {
+        Double::hash_f64(self.val)
+    }
+
     fn to_strval(&self, vm: &VM) -> Result<Val, Box<VMError>> {
-        let mut buf = ryu::Buffer::new();
-        Ok(String_::new(vm, buf.format(self.val).to_owned(), true))
+        // ryu's formatting of non-finite values is not guaranteed, so we print them ourselves in
+        // the same form that `Double class>>fromString:` accepts.
+        let s = if self.val.is_nan() {
+            "NaN".to_owned()
+        } else if self.val.is_infinite() {
+            if self.val > 0f64 {
+                "Infinity".to_owned()
+            } else {
+                "-Infinity".to_owned()
+            }
+        } else {
+            let mut buf = ryu::Buffer::new();
+            buf.format_finite(self.val).to_owned()
+        };
+        Ok(String_::new(vm, s))
     }
 
     fn add(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
@@ -48,6 +71,10 @@ impl Obj for Double {
         }
     }
 
+    fn div(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        self.double_div(vm, other)
+    }
+
     fn double_div(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
         if let Some(rhs) = other.as_isize(vm) {
             if rhs == 0 {
@@ -192,4 +219,47 @@ impl Double {
     pub fn double(&self) -> f64 {
         self.val
     }
+
+    /// Return the hash of the `Double` whose value is `val`.
+    pub fn hash_f64(val: f64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        // Doubles with no fractional part are equal to the equivalent integer, so they must have
+        // the same hash as `Int`/`ArbInt` (and tagged integers). This also ensures that 0.0 and
+        // -0.0 have the same hash.
+        if val.is_finite() && val.fract() == 0f64 {
+            if let Some(i) = val.to_isize() {
+                i.hash(&mut hasher);
+            } else {
+                BigInt::from_f64(val).unwrap().hash(&mut hasher);
+            }
+        } else {
+            val.to_bits().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Convert `val`, which must have no fractional part, into a SOM integer, creating an `ArbInt`
+    /// if necessary. Returns an error if `val` is NaN or infinite.
+    pub fn f64_to_int(vm: &VM, val: f64) -> Result<Val, Box<VMError>> {
+        if let Some(i) = val.to_isize() {
+            Val::from_isize(vm, i)
+        } else {
+            match BigInt::from_f64(val) {
+                Some(i) => ArbInt::new(vm, i),
+                None => Err(Box::new(VMError::CantRepresentAsBigInt)),
+            }
+        }
+    }
+
+    /// Parse `s` as a `f64`, returning `None` if `s` is not a valid number. As well as the usual
+    /// decimal and exponent notations, this accepts `NaN`, `Infinity` and `-Infinity`, which are
+    /// the forms that `Double`s print non-finite values as.
+    pub fn parse(s: &str) -> Option<f64> {
+        match s.trim() {
+            "NaN" => Some(std::f64::NAN),
+            "Infinity" => Some(std::f64::INFINITY),
+            "-Infinity" => Some(std::f64::NEG_INFINITY),
+            s => s.parse::<f64>().ok().filter(|d| d.is_finite()),
+        }
+    }
 }
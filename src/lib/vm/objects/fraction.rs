@@ -0,0 +1,272 @@
+//! Exact rational numbers. A [`Fraction`](Fraction) is always normalised (i.e. its numerator and
+//! denominator have no common factors and its denominator is positive) and never represents a
+//! whole number: operations whose result is a whole number produce an integer instead.
+
+#![allow(clippy::new_ret_no_self)]
+
+use std::{
+    cmp::Ordering,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use abgc_derive::GcLayout;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, ToPrimitive, Zero};
+
+use crate::vm::{
+    core::{VMError, VM},
+    objects::{ArbInt, Double, Obj, ObjType, StaticObjType, String_},
+    val::{NotUnboxable, Val},
+};
+
+/// The arithmetic operations that can be performed on a `Fraction`.
+#[derive(Clone, Copy, Debug)]
+pub enum FractionOp {
+    Add,
+    Sub,
+    Mul,
+    /// Exact division.
+    Div,
+    /// Division producing a `Double`.
+    DoubleDiv,
+}
+
+#[derive(Debug, GcLayout)]
+/// A boxed rational number.
+pub struct Fraction {
+    val: BigRational,
+}
+
+impl NotUnboxable for Fraction {}
+
+impl Obj for Fraction {
+    fn dyn_objtype(&self) -> ObjType {
+        ObjType::Fraction
+    }
+
+    fn get_class(&self, vm: &VM) -> Val {
+        vm.fraction_cls.clone()
+    }
+
+    fn hashcode(&self) -> u64 {
+        // A fraction is equal to a `Double` if it converts to the same `f64` (e.g. `1/2 = 0.5`),
+        // so in that case it must have the same hash as the `Double`.
+        if let Ok(d) = self.to_f64() {
+            return Double::hash_f64(d);
+        }
+        let mut hasher = DefaultHasher::new();
+        self.val.numer().hash(&mut hasher);
+        self.val.denom().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn to_strval(&self, vm: &VM) -> Result<Val, Box<VMError>> {
+        Ok(String_::new(
+            vm,
+            format!("{}/{}", self.val.numer(), self.val.denom()),
+        ))
+    }
+
+    fn add(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        Fraction::arith(vm, &Val::recover(self), &other, FractionOp::Add)
+    }
+
+    fn div(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        Fraction::arith(vm, &Val::recover(self), &other, FractionOp::Div)
+    }
+
+    fn double_div(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        Fraction::arith(vm, &Val::recover(self), &other, FractionOp::DoubleDiv)
+    }
+
+    fn mul(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        Fraction::arith(vm, &Val::recover(self), &other, FractionOp::Mul)
+    }
+
+    fn sub(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        Fraction::arith(vm, &Val::recover(self), &other, FractionOp::Sub)
+    }
+
+    fn ref_equals(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        self.equals(vm, other)
+    }
+
+    fn equals(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        let b = match Fraction::compare(vm, &Val::recover(self), &other) {
+            Ok(o) => o == Some(Ordering::Equal),
+            Err(_) => false,
+        };
+        Ok(Val::from_bool(vm, b))
+    }
+
+    fn not_equals(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        let b = match Fraction::compare(vm, &Val::recover(self), &other) {
+            Ok(o) => o != Some(Ordering::Equal),
+            Err(_) => true,
+        };
+        Ok(Val::from_bool(vm, b))
+    }
+
+    fn greater_than(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        let o = Fraction::compare(vm, &Val::recover(self), &other)?;
+        Ok(Val::from_bool(vm, o == Some(Ordering::Greater)))
+    }
+
+    fn greater_than_equals(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        let o = Fraction::compare(vm, &Val::recover(self), &other)?;
+        Ok(Val::from_bool(
+            vm,
+            o == Some(Ordering::Greater) || o == Some(Ordering::Equal),
+        ))
+    }
+
+    fn less_than(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        let o = Fraction::compare(vm, &Val::recover(self), &other)?;
+        Ok(Val::from_bool(vm, o == Some(Ordering::Less)))
+    }
+
+    fn less_than_equals(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        let o = Fraction::compare(vm, &Val::recover(self), &other)?;
+        Ok(Val::from_bool(
+            vm,
+            o == Some(Ordering::Less) || o == Some(Ordering::Equal),
+        ))
+    }
+}
+
+impl StaticObjType for Fraction {
+    fn static_objtype() -> ObjType {
+        ObjType::Fraction
+    }
+}
+
+impl Fraction {
+    /// Create a `Val` representing the rational number `val`: if `val` is a whole number, this
+    /// will be an integer rather than a `Fraction`.
+    pub fn new(vm: &VM, val: BigRational) -> Result<Val, Box<VMError>> {
+        if val.is_integer() {
+            ArbInt::new(vm, val.to_integer())
+        } else {
+            Ok(Val::from_obj(vm, Fraction { val }))
+        }
+    }
+
+    /// Create a `Val` representing `numer / denom`, both of which must be integers.
+    pub fn from_vals(vm: &VM, numer: Val, denom: Val) -> Result<Val, Box<VMError>> {
+        let n = Fraction::to_bigint(vm, &numer)?;
+        let d = Fraction::to_bigint(vm, &denom)?;
+        if d.is_zero() {
+            return Err(Box::new(VMError::DivisionByZero));
+        }
+        Fraction::new(vm, BigRational::new(n, d))
+    }
+
+    pub fn ratio(&self) -> &BigRational {
+        &self.val
+    }
+
+    /// Convert this fraction to the nearest `f64`.
+    pub fn to_f64(&self) -> Result<f64, Box<VMError>> {
+        Fraction::ratio_to_f64(&self.val)
+    }
+
+    /// Perform `op` on `lhs` and `rhs`, at least one of which must be a `Fraction`. If the other
+    /// is an integer or `Fraction` the result is exact; if it is a `Double`, the result is a
+    /// `Double`.
+    pub fn arith(vm: &VM, lhs: &Val, rhs: &Val, op: FractionOp) -> Result<Val, Box<VMError>> {
+        if let (Some(x), Some(y)) = (Fraction::to_ratio(vm, lhs), Fraction::to_ratio(vm, rhs)) {
+            return match op {
+                FractionOp::Add => Fraction::new(vm, x + y),
+                FractionOp::Sub => Fraction::new(vm, x - y),
+                FractionOp::Mul => Fraction::new(vm, x * y),
+                FractionOp::Div | FractionOp::DoubleDiv if y.is_zero() => {
+                    Err(Box::new(VMError::DivisionByZero))
+                }
+                FractionOp::Div => Fraction::new(vm, x / y),
+                FractionOp::DoubleDiv => Ok(Double::new(vm, Fraction::ratio_to_f64(&(x / y))?)),
+            };
+        }
+        let x = Fraction::rcv_to_f64_val(vm, lhs)?;
+        let y = Fraction::to_f64_val(vm, rhs)?;
+        match op {
+            FractionOp::Add => Ok(Double::new(vm, x + y)),
+            FractionOp::Sub => Ok(Double::new(vm, x - y)),
+            FractionOp::Mul => Ok(Double::new(vm, x * y)),
+            FractionOp::Div | FractionOp::DoubleDiv if y == 0f64 => {
+                Err(Box::new(VMError::DivisionByZero))
+            }
+            FractionOp::Div | FractionOp::DoubleDiv => Ok(Double::new(vm, x / y)),
+        }
+    }
+
+    /// Compare `lhs` and `rhs`, at least one of which must be a `Fraction`, returning `None` if
+    /// the other is a NaN `Double`. Returns an error if either is not a number.
+    pub fn compare(vm: &VM, lhs: &Val, rhs: &Val) -> Result<Option<Ordering>, Box<VMError>> {
+        if let (Some(x), Some(y)) = (Fraction::to_ratio(vm, lhs), Fraction::to_ratio(vm, rhs)) {
+            return Ok(Some(x.cmp(&y)));
+        }
+        let x = Fraction::rcv_to_f64_val(vm, lhs)?;
+        let y = Fraction::to_f64_val(vm, rhs)?;
+        Ok(x.partial_cmp(&y))
+    }
+
+    /// If `v` is an integer or a `Fraction`, return it as a `BigRational`.
+    fn to_ratio(vm: &VM, v: &Val) -> Option<BigRational> {
+        if let Some(f) = v.try_downcast::<Fraction>(vm) {
+            Some(f.val.clone())
+        } else {
+            Fraction::to_bigint(vm, v)
+                .ok()
+                .map(BigRational::from_integer)
+        }
+    }
+
+    fn to_bigint(vm: &VM, v: &Val) -> Result<BigInt, Box<VMError>> {
+        if let Some(i) = v.as_isize(vm) {
+            Ok(BigInt::from_isize(i).unwrap())
+        } else if let Some(i) = v.try_downcast::<ArbInt>(vm) {
+            Ok(i.bigint().clone())
+        } else {
+            Err(Box::new(VMError::TypeError {
+                expected: ObjType::Int,
+                got: v.dyn_objtype(vm),
+            }))
+        }
+    }
+
+    /// Convert `v`, the receiver of a numeric operation, to an `f64`.
+    fn rcv_to_f64_val(vm: &VM, v: &Val) -> Result<f64, Box<VMError>> {
+        Fraction::to_f64_val(vm, v).map_err(|e| match *e {
+            VMError::NotANumber { got } => Box::new(VMError::ReceiverNotANumber { got }),
+            e => Box::new(e),
+        })
+    }
+
+    /// Convert the number `v` to an `f64`.
+    fn to_f64_val(vm: &VM, v: &Val) -> Result<f64, Box<VMError>> {
+        if let Some(d) = v.try_downcast::<Double>(vm) {
+            Ok(d.double())
+        } else if let Some(f) = v.try_downcast::<Fraction>(vm) {
+            f.to_f64()
+        } else if let Some(i) = v.as_isize(vm) {
+            Ok(i as f64)
+        } else if let Some(i) = v.try_downcast::<ArbInt>(vm) {
+            i.bigint()
+                .to_f64()
+                .ok_or_else(|| Box::new(VMError::CantRepresentAsDouble))
+        } else {
+            Err(Box::new(VMError::NotANumber {
+                got: v.dyn_objtype(vm),
+            }))
+        }
+    }
+
+    fn ratio_to_f64(val: &BigRational) -> Result<f64, Box<VMError>> {
+        match (val.numer().to_f64(), val.denom().to_f64()) {
+            (Some(n), Some(d)) if n.is_finite() && d.is_finite() => Ok(n / d),
+            _ => Err(Box::new(VMError::CantRepresentAsDouble)),
+        }
+    }
+}
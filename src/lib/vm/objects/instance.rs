@@ -1,6 +1,9 @@
 #![allow(clippy::new_ret_no_self)]
 
-use std::cell::UnsafeCell;
+use std::{
+    cell::UnsafeCell,
+    mem::{size_of, size_of_val},
+};
 
 use abgc_derive::GcLayout;
 
@@ -25,6 +28,10 @@ impl Obj for Inst {
     fn get_class(&self, _: &VM) -> Val {
         self.class.clone()
     }
+
+    fn object_size(&self) -> usize {
+        size_of_val(self) + self.num_inst_vars() * size_of::<Val>()
+    }
 }
 
 impl NotUnboxable for Inst {}
@@ -38,8 +45,11 @@ impl StaticObjType for Inst {
 impl Inst {
     pub fn new(vm: &VM, class: Val) -> Val {
         let cls: &Class = class.downcast(vm).unwrap();
-        let mut inst_vars = Vec::with_capacity(cls.num_inst_vars);
-        inst_vars.resize(cls.num_inst_vars, Val::illegal());
+        // Instance variables which have not yet been assigned to are `nil`. Note that we may be
+        // creating `nil` itself, but since `Nil` has no instance variables, `vm.nil` is never read
+        // in that case.
+        let mut inst_vars = Vec::with_capacity(cls.inst_vars.len());
+        inst_vars.resize(cls.inst_vars.len(), vm.nil.clone());
         let inst = Inst {
             class,
             inst_vars: UnsafeCell::new(inst_vars),
@@ -47,6 +57,11 @@ impl Inst {
         Val::from_obj(vm, inst)
     }
 
+    /// How many instance variables does this instance have?
+    pub fn num_inst_vars(&self) -> usize {
+        unsafe { &*self.inst_vars.get() }.len()
+    }
+
     pub fn inst_var_lookup(&self, n: usize) -> Val {
         let inst_vars = unsafe { &mut *self.inst_vars.get() };
         inst_vars[n].clone()
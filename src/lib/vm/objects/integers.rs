@@ -11,11 +11,16 @@
 
 #![allow(clippy::new_ret_no_self)]
 
-use std::convert::TryFrom;
+use std::{
+    collections::hash_map::DefaultHasher,
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+};
 
 use abgc_derive::GcLayout;
 use num_bigint::BigInt;
-use num_traits::{FromPrimitive, ToPrimitive, Zero};
+use num_integer::Integer;
+use num_traits::{FromPrimitive, One, ToPrimitive, Zero};
 
 use crate::vm::{
     core::{VMError, VM},
@@ -40,8 +45,14 @@ impl Obj for ArbInt {
         vm.int_cls.clone()
     }
 
+    fn hashcode(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.val.hash(&mut hasher);
+        hasher.finish()
+    }
+
     fn to_strval(&self, vm: &VM) -> Result<Val, Box<VMError>> {
-        Ok(String_::new(vm, self.val.to_string(), true))
+        Ok(String_::new(vm, self.val.to_string()))
     }
 
     fn add(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
@@ -172,6 +183,35 @@ impl Obj for ArbInt {
         }
     }
 
+    fn or(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        if let Some(rhs) = other.as_isize(vm) {
+            ArbInt::new(vm, &self.val | BigInt::from_isize(rhs).unwrap())
+        } else if let Some(rhs) = other.try_downcast::<ArbInt>(vm) {
+            ArbInt::new(vm, &self.val | &rhs.val)
+        } else {
+            Err(Box::new(VMError::TypeError {
+                expected: self.dyn_objtype(),
+                got: other.dyn_objtype(vm),
+            }))
+        }
+    }
+
+    fn rem(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        if let Some(rhs) = other.as_isize(vm) {
+            if rhs == 0 {
+                Err(Box::new(VMError::DivisionByZero))
+            } else {
+                ArbInt::new(vm, &self.val % rhs)
+            }
+        } else if let Some(rhs) = other.try_downcast::<ArbInt>(vm) {
+            ArbInt::new(vm, &self.val % &rhs.val)
+        } else {
+            Err(Box::new(VMError::NotANumber {
+                got: other.dyn_objtype(vm),
+            }))
+        }
+    }
+
     fn shl(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
         if let Some(rhs) = other.as_isize(vm) {
             if rhs < 0 {
@@ -189,6 +229,32 @@ impl Obj for ArbInt {
         }
     }
 
+    fn shr(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        // If every bit is shifted out, the result depends only on the sign of `self`.
+        let all_out = if self.val < Zero::zero() { -1 } else { 0 };
+        if let Some(rhs) = other.as_isize(vm) {
+            if rhs < 0 {
+                Err(Box::new(VMError::NegativeShift))
+            } else if rhs as usize >= self.val.bits() {
+                Val::from_isize(vm, all_out)
+            } else {
+                // `BigInt`'s `>>` rounds towards zero, whereas we want the same semantics as an
+                // arithmetic shift on an `isize` (i.e. rounding towards negative infinity).
+                ArbInt::new(vm, self.val.div_floor(&(BigInt::one() << rhs as usize)))
+            }
+        } else if let Some(rhs) = other.try_downcast::<ArbInt>(vm) {
+            if rhs.val < Zero::zero() {
+                Err(Box::new(VMError::NegativeShift))
+            } else {
+                Val::from_isize(vm, all_out)
+            }
+        } else {
+            Err(Box::new(VMError::NotANumber {
+                got: other.dyn_objtype(vm),
+            }))
+        }
+    }
+
     fn sqrt(&self, vm: &VM) -> Result<Val, Box<VMError>> {
         if self.val < Zero::zero() {
             Err(Box::new(VMError::DomainError))
@@ -361,8 +427,15 @@ impl Obj for Int {
         vm.int_cls.clone()
     }
 
+    fn hashcode(&self) -> u64 {
+        // This must produce the same hash as `Val::hashcode` does for tagged integers.
+        let mut hasher = DefaultHasher::new();
+        self.val.hash(&mut hasher);
+        hasher.finish()
+    }
+
     fn to_strval(&self, vm: &VM) -> Result<Val, Box<VMError>> {
-        Ok(String_::new(vm, self.val.to_string(), true))
+        Ok(String_::new(vm, self.val.to_string()))
     }
 
     fn add(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
@@ -1,5 +1,7 @@
 #![allow(clippy::new_ret_no_self)]
 
+use std::cell::UnsafeCell;
+
 use abgc_derive::GcLayout;
 
 use crate::{
@@ -15,12 +17,28 @@ use crate::{
 pub struct Method {
     pub name: String,
     pub body: MethodBody,
+    /// The class this method is defined in. This is set (once) by `Class::bind_methods` after
+    /// the class has been created.
+    holder: UnsafeCell<Val>,
 }
 
 #[derive(Debug)]
 pub enum MethodBody {
     /// A built-in primitive.
     Primitive(Primitive),
+    /// A built-in primitive with user bytecode which is executed (with the same receiver and
+    /// arguments) if the primitive fails.
+    PrimitiveWithFallback {
+        prim: Primitive,
+        /// How many variables does the fallback code define?
+        num_vars: usize,
+        /// The offset of the fallback code's bytecode in its parent class.
+        bytecode_off: usize,
+        max_stack: usize,
+        /// Are any of this method's variables captured by a block? If not, its variables are
+        /// stored on the stack rather than in a heap-allocated closure.
+        needs_closure: bool,
+    },
     /// User bytecode.
     User {
         /// How many variables does this method define?
@@ -28,6 +46,9 @@ pub enum MethodBody {
         /// The offset of this method's bytecode in its parent class.
         bytecode_off: usize,
         max_stack: usize,
+        /// Are any of this method's variables captured by a block? If not, its variables are
+        /// stored on the stack rather than in a heap-allocated closure.
+        needs_closure: bool,
     },
 }
 
@@ -36,13 +57,53 @@ impl Obj for Method {
         ObjType::Method
     }
 
-    fn get_class(&self, _: &VM) -> Val {
-        unimplemented!();
+    fn get_class(&self, vm: &VM) -> Val {
+        match self.body {
+            MethodBody::Primitive(_) | MethodBody::PrimitiveWithFallback { .. } => {
+                vm.primitive_cls.clone()
+            }
+            MethodBody::User { .. } => vm.method_cls.clone(),
+        }
     }
 }
 
 impl NotUnboxable for Method {}
 
+impl Method {
+    pub fn new(name: String, body: MethodBody) -> Method {
+        Method {
+            name,
+            body,
+            holder: UnsafeCell::new(Val::illegal()),
+        }
+    }
+
+    /// Return the class this method is defined in.
+    pub fn holder(&self) -> Val {
+        unsafe { &*self.holder.get() }.clone()
+    }
+
+    /// Set the class this method is defined in to `holder`.
+    pub fn set_holder(&self, holder: Val) {
+        *unsafe { &mut *self.holder.get() } = holder;
+    }
+
+    /// How many arguments does this method take?
+    pub fn num_args(&self) -> usize {
+        Method::selector_num_args(&self.name)
+    }
+
+    /// How many arguments does a method with the selector `sel` take? Binary selectors (e.g.
+    /// `+`) take one argument, keyword selectors (e.g. `at:put:`) take one argument per keyword,
+    /// and unary selectors take none.
+    pub fn selector_num_args(sel: &str) -> usize {
+        match sel.chars().next() {
+            Some(c) if !c.is_alphanumeric() && c != '_' => 1,
+            _ => sel.matches(':').count(),
+        }
+    }
+}
+
 impl StaticObjType for Method {
     fn static_objtype() -> ObjType {
         ObjType::Method
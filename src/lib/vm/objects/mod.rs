@@ -20,21 +20,35 @@
 //! Although this constraint is not enforced through the type system, it is not hard to obey: as
 //! soon as you create an `Obj` instance, pass it to `Val::from_obj`.
 
+mod array;
 mod block;
 mod class;
+mod dictionary;
 mod double;
+mod fraction;
 mod instance;
 mod integers;
 mod method;
+mod stream;
 mod string_;
+mod symbol;
+mod vector;
 
+pub use array::Array;
 pub use block::{Block, BlockInfo};
 pub use class::Class;
+pub use dictionary::Dictionary;
 pub use double::Double;
+pub use fraction::{Fraction, FractionOp};
 pub use instance::Inst;
 pub use integers::{ArbInt, Int};
 pub use method::{Method, MethodBody};
+pub use stream::{ReadStream, WriteStream};
 pub use string_::String_;
+pub use symbol::Symbol;
+pub use vector::Vector;
+
+use std::mem::size_of_val;
 
 use abgc::{self, Gc};
 use natrob::narrowable_abgc;
@@ -48,13 +62,20 @@ use crate::vm::{
 #[derive(Debug, PartialEq)]
 pub enum ObjType {
     ArbInt,
+    Array,
     Block,
     Class,
+    Dictionary,
     Double,
+    Fraction,
     Method,
     Inst,
     Int,
+    ReadStream,
     String_,
+    Symbol,
+    Vector,
+    WriteStream,
 }
 
 /// The main SOM Object trait. Notice that code should almost never call these functions directly:
@@ -66,58 +87,119 @@ pub trait Obj: std::fmt::Debug + abgc::GcLayout {
     /// What class is this object an instance of?
     fn get_class(&self, vm: &VM) -> Val;
 
+    /// Return this object's hashcode. By default this is an identity hash: since abgc never moves
+    /// objects, an object's address is stable for its entire lifetime.
+    fn hashcode(&self) -> u64 {
+        self as *const _ as *const u8 as u64
+    }
+
+    /// How many bytes does this object occupy? This includes memory directly owned by the object
+    /// (e.g. a string's characters) but not other SOM objects that it references.
+    fn object_size(&self) -> usize {
+        size_of_val(self)
+    }
+
     /// Convert this object to a `Val` that represents a SOM string.
     fn to_strval(&self, _: &VM) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::TypeError {
+            expected: ObjType::String_,
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Produce a new `Val` which adds `other` to this.
     fn add(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Produce a new `Val` which performs a bitwise and with `other` and this.
     fn and(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::TypeError {
+            expected: ObjType::Int,
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Produce a new `Val` which divides `other` from this.
     fn div(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
     }
 
     fn double_div(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Produce a new `Val` which performs a mod operation on this with `other`.
     fn modulus(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Produce a new `Val` which multiplies `other` to this.
     fn mul(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
+    }
+
+    /// Produce a new `Val` which performs a bitwise or with `other` and this.
+    fn or(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
+        Err(Box::new(VMError::TypeError {
+            expected: ObjType::Int,
+            got: self.dyn_objtype(),
+        }))
+    }
+
+    /// Produce a new `Val` which is the remainder of dividing this by `other`.
+    fn rem(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Produce a new `Val` which shifts `self` `other` bits to the left.
     fn shl(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::TypeError {
+            expected: ObjType::Int,
+            got: self.dyn_objtype(),
+        }))
+    }
+
+    /// Produce a new `Val` which arithmetically shifts `self` `other` bits to the right.
+    fn shr(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
+        Err(Box::new(VMError::TypeError {
+            expected: ObjType::Int,
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Produces a new `Val` which is the square root of this.
     fn sqrt(&self, _: &VM) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Produce a new `Val` which subtracts `other` from this.
     fn sub(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Produce a new `Val` which performs a bitwise xor with `other` and this
     fn xor(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::TypeError {
+            expected: ObjType::Int,
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Is this `Val` reference equality equal to `other`? Only number types are likely to want to
@@ -132,34 +214,43 @@ pub trait Obj: std::fmt::Debug + abgc::GcLayout {
         ))
     }
 
-    /// Does this `Val` equal `other`?
-    fn equals(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+    /// Does this `Val` equal `other`? By default this is reference equality.
+    fn equals(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        self.ref_equals(vm, other)
     }
 
     /// Does this `Val` not equal `other`?
-    fn not_equals(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+    fn not_equals(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        let b = self.equals(vm, other)?.bit_eq(&vm.true_);
+        Ok(Val::from_bool(vm, !b))
     }
 
     /// Is this `Val` greater than `other`?
     fn greater_than(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Is this `Val` greater than or equal to `other`?
     fn greater_than_equals(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Is this `Val` less than `other`?
     fn less_than(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
     }
 
     /// Is this `Val` less than or equal to `other`?
     fn less_than_equals(&self, _: &VM, _: Val) -> Result<Val, Box<VMError>> {
-        unimplemented!();
+        Err(Box::new(VMError::ReceiverNotANumber {
+            got: self.dyn_objtype(),
+        }))
     }
 }
 
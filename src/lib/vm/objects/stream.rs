@@ -0,0 +1,216 @@
+#![allow(clippy::new_ret_no_self)]
+
+use std::{
+    cell::{Cell, UnsafeCell},
+    mem::{size_of, size_of_val},
+};
+
+use abgc_derive::GcLayout;
+
+use crate::vm::{
+    core::{VMError, VM},
+    objects::{Array, Obj, ObjType, StaticObjType, String_},
+    val::{NotUnboxable, Val},
+};
+
+/// A stream which reads, in order, the elements of a string (one character at a time) or an
+/// array.
+#[derive(Debug, GcLayout)]
+pub struct ReadStream {
+    /// The `String_` or `Array` being read from.
+    coll: Val,
+    /// The position of the next element to be read: for strings this is a byte offset (which is
+    /// always on a character boundary); for arrays it is a 0-based index.
+    pos: Cell<usize>,
+}
+
+impl Obj for ReadStream {
+    fn dyn_objtype(&self) -> ObjType {
+        ObjType::ReadStream
+    }
+
+    fn get_class(&self, vm: &VM) -> Val {
+        vm.read_stream_cls.clone()
+    }
+}
+
+impl NotUnboxable for ReadStream {}
+
+impl StaticObjType for ReadStream {
+    fn static_objtype() -> ObjType {
+        ObjType::ReadStream
+    }
+}
+
+impl ReadStream {
+    /// Create a new stream which reads from `coll`, which must be a `String_` or an `Array`.
+    pub fn new(vm: &VM, coll: Val) -> Result<Val, Box<VMError>> {
+        if coll.try_downcast::<String_>(vm).is_none() {
+            coll.downcast::<Array>(vm)?;
+        }
+        Ok(Val::from_obj(
+            vm,
+            ReadStream {
+                coll,
+                pos: Cell::new(0),
+            },
+        ))
+    }
+
+    /// Have all elements been read?
+    pub fn at_end(&self, vm: &VM) -> bool {
+        match self.coll.try_downcast::<String_>(vm) {
+            Some(s) => self.pos.get() >= s.as_str().len(),
+            None => self.pos.get() >= self.coll.downcast::<Array>(vm).unwrap().length(),
+        }
+    }
+
+    /// Return the next element without consuming it, or `nil` if the stream is at its end.
+    pub fn peek(&self, vm: &VM) -> Val {
+        match self.peek_elem(vm) {
+            Some((v, _)) => v,
+            None => vm.nil.clone(),
+        }
+    }
+
+    /// Consume and return the next element, or `nil` if the stream is at its end.
+    pub fn next(&self, vm: &VM) -> Val {
+        match self.peek_elem(vm) {
+            Some((v, len)) => {
+                self.pos.set(self.pos.get() + len);
+                v
+            }
+            None => vm.nil.clone(),
+        }
+    }
+
+    /// Consume elements up to, and including, the first element equal to `delim`, returning
+    /// a collection (of the same kind as the stream's underlying collection) of the elements
+    /// before `delim`. If `delim` is not found, the rest of the stream is returned.
+    pub fn up_to(&self, vm: &VM, delim: Val) -> Result<Val, Box<VMError>> {
+        if let Some(s) = self.coll.try_downcast::<String_>(vm) {
+            let delim_str = String_::from_val(vm, &delim)?;
+            let rest = &s.as_str()[self.pos.get()..];
+            let (found, skip) = match rest.find(delim_str.as_str()) {
+                Some(off) => (&rest[..off], off + delim_str.as_str().len()),
+                None => (rest, rest.len()),
+            };
+            self.pos.set(self.pos.get() + skip);
+            return Ok(String_::new(vm, found.to_owned()));
+        }
+        let mut found = Vec::new();
+        while let Some((v, _)) = self.peek_elem(vm) {
+            self.pos.set(self.pos.get() + 1);
+            // Since `=` can run arbitrary SOM code, no references to the array are held here.
+            if vm
+                .send(v.clone(), "=", vec![delim.clone()])?
+                .bit_eq(&vm.true_)
+            {
+                break;
+            }
+            found.push(v);
+        }
+        Ok(Array::from_vec(vm, found))
+    }
+
+    /// Return the next element and how far `pos` must be advanced to consume it, or `None` if
+    /// the stream is at its end.
+    fn peek_elem(&self, vm: &VM) -> Option<(Val, usize)> {
+        match self.coll.try_downcast::<String_>(vm) {
+            Some(s) => {
+                let c = s.as_str()[self.pos.get()..].chars().next()?;
+                Some((String_::new(vm, c.to_string()), c.len_utf8()))
+            }
+            None => {
+                let arr = self.coll.downcast::<Array>(vm).unwrap();
+                Some((arr.get(self.pos.get())?, 1))
+            }
+        }
+    }
+}
+
+/// The buffer a `WriteStream` writes to.
+#[derive(Debug)]
+enum WriteBuf {
+    Str(String),
+    Arr(Vec<Val>),
+}
+
+/// A stream which efficiently builds up a new string or array.
+#[derive(Debug, GcLayout)]
+pub struct WriteStream {
+    buf: UnsafeCell<WriteBuf>,
+}
+
+impl Obj for WriteStream {
+    fn dyn_objtype(&self) -> ObjType {
+        ObjType::WriteStream
+    }
+
+    fn get_class(&self, vm: &VM) -> Val {
+        vm.write_stream_cls.clone()
+    }
+
+    fn object_size(&self) -> usize {
+        size_of_val(self)
+            + match unsafe { &*self.buf.get() } {
+                WriteBuf::Str(s) => s.capacity(),
+                WriteBuf::Arr(a) => a.capacity() * size_of::<Val>(),
+            }
+    }
+}
+
+impl NotUnboxable for WriteStream {}
+
+impl StaticObjType for WriteStream {
+    fn static_objtype() -> ObjType {
+        ObjType::WriteStream
+    }
+}
+
+impl WriteStream {
+    /// Create a new, empty, stream which produces a collection of the same kind as `coll` (which
+    /// must be a `String_` or an `Array`). Note that, as is traditional, the contents of `coll`
+    /// itself are ignored.
+    pub fn new(vm: &VM, coll: Val) -> Result<Val, Box<VMError>> {
+        let buf = if coll.try_downcast::<String_>(vm).is_some() {
+            WriteBuf::Str(String::new())
+        } else {
+            coll.downcast::<Array>(vm)?;
+            WriteBuf::Arr(Vec::new())
+        };
+        Ok(Val::from_obj(
+            vm,
+            WriteStream {
+                buf: UnsafeCell::new(buf),
+            },
+        ))
+    }
+
+    /// Append `v` to the stream. If this stream is building a string, `v` must be a string.
+    pub fn next_put(&self, vm: &VM, v: Val) -> Result<(), Box<VMError>> {
+        match unsafe { &mut *self.buf.get() } {
+            WriteBuf::Str(s) => s.push_str(String_::from_val(vm, &v)?.as_str()),
+            WriteBuf::Arr(a) => a.push(v),
+        }
+        Ok(())
+    }
+
+    /// Append each element of `coll` (which must be of the same kind as the collection this
+    /// stream is building) to the stream.
+    pub fn next_put_all(&self, vm: &VM, coll: Val) -> Result<(), Box<VMError>> {
+        match unsafe { &mut *self.buf.get() } {
+            WriteBuf::Str(s) => s.push_str(String_::from_val(vm, &coll)?.as_str()),
+            WriteBuf::Arr(a) => a.extend(coll.downcast::<Array>(vm)?.to_vec()),
+        }
+        Ok(())
+    }
+
+    /// Return a new collection containing everything written to this stream so far.
+    pub fn contents(&self, vm: &VM) -> Val {
+        match unsafe { &*self.buf.get() } {
+            WriteBuf::Str(s) => String_::new(vm, s.clone()),
+            WriteBuf::Arr(a) => Array::from_vec(vm, a.clone()),
+        }
+    }
+}
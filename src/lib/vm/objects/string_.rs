@@ -1,19 +1,29 @@
 #![allow(clippy::new_ret_no_self)]
 
-use std::str;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    mem::size_of_val,
+    str::{self, FromStr},
+};
 
 use abgc_derive::GcLayout;
+use num_bigint::BigInt;
 
 use crate::vm::{
     core::{VMError, VM},
-    objects::{Obj, ObjType, StaticObjType},
+    objects::{ArbInt, Array, Obj, ObjType, StaticObjType, Symbol},
     val::{NotUnboxable, Val},
 };
 
+/// A SOM string. Strings are sequences of Unicode code points: all indexing operations (e.g.
+/// `length` and `at:`) count code points, not bytes or grapheme clusters.
 #[derive(Debug, GcLayout)]
 pub struct String_ {
     s: String,
-    is_str: bool,
+    /// Is every character in `s` ASCII? If so, code point indexes and byte offsets are the same,
+    /// so indexing operations can be performed in O(1) time.
+    is_ascii: bool,
 }
 
 impl Obj for String_ {
@@ -22,25 +32,45 @@ impl Obj for String_ {
     }
 
     fn get_class(&self, vm: &VM) -> Val {
-        // FIXME This is a temporary hack until we sort out bootstrapping of the String_ class
-        if self.is_str {
-            vm.str_cls.clone()
-        } else {
-            vm.sym_cls.clone()
-        }
+        vm.str_cls.clone()
+    }
+
+    fn hashcode(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.s.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn object_size(&self) -> usize {
+        size_of_val(self) + self.s.len()
     }
 
     fn to_strval(&self, vm: &VM) -> Result<Val, Box<VMError>> {
-        Ok(String_::new(vm, self.s.to_string(), true))
+        Ok(String_::new(vm, self.s.to_string()))
     }
 
     fn ref_equals(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
-        let other_str: &String_ = other.downcast(vm)?;
+        // A string is never reference equal to a symbol, even if they have the same characters.
+        let b = match other.try_downcast::<String_>(vm) {
+            Some(other_str) => self.s == other_str.s,
+            None => false,
+        };
+        Ok(Val::from_bool(vm, b))
+    }
 
-        Ok(Val::from_bool(
-            vm,
-            (self.is_str == other_str.is_str) && (self.s == other_str.s),
-        ))
+    fn equals(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        // Strings and symbols with the same characters are equal (though not reference equal).
+        let b = match String_::from_val(vm, &other) {
+            Ok(other_str) => self.s == other_str.s,
+            Err(_) => false,
+        };
+        Ok(Val::from_bool(vm, b))
+    }
+
+    fn less_than(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        // Comparing UTF-8 strings bytewise is equivalent to comparing their code points.
+        let other_str = String_::from_val(vm, &other)?;
+        Ok(Val::from_bool(vm, self.s < other_str.s))
     }
 }
 
@@ -53,8 +83,18 @@ impl StaticObjType for String_ {
 }
 
 impl String_ {
-    pub fn new(vm: &VM, s: String, is_str: bool) -> Val {
-        Val::from_obj(vm, String_ { s, is_str })
+    pub fn new(vm: &VM, s: String) -> Val {
+        let is_ascii = s.is_ascii();
+        Val::from_obj(vm, String_ { s, is_ascii })
+    }
+
+    /// Return the characters of `v`, which must be either a `String_` or a `Symbol`, as a
+    /// `String_`.
+    pub fn from_val<'a>(vm: &VM, v: &'a Val) -> Result<&'a String_, Box<VMError>> {
+        match v.try_downcast::<Symbol>(vm) {
+            Some(sym) => Ok(sym.string(vm)),
+            None => v.downcast(vm),
+        }
     }
 
     pub fn as_str(&self) -> &str {
@@ -63,12 +103,12 @@ impl String_ {
 
     /// Concatenate this string with another string and return the result.
     pub fn concatenate(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
-        let other_str: &String_ = other.downcast(vm)?;
+        let other_str = String_::from_val(vm, &other)?;
 
         // Since strings are immutable, concatenating an empty string means we don't need to
         // make a new string.
         if self.s.is_empty() {
-            return Ok(other);
+            return Ok(Val::recover(other_str));
         } else if other_str.s.is_empty() {
             return Ok(Val::recover(self));
         }
@@ -76,10 +116,133 @@ impl String_ {
         let mut new = String::with_capacity(self.s.len() + other_str.s.len());
         new.push_str(&self.s);
         new.push_str(&other_str.s);
-        Ok(String_::new(vm, new, true))
+        Ok(String_::new(vm, new))
+    }
+
+    /// Return the (interned) symbol with the same characters as this string.
+    pub fn to_symbol(&self, vm: &VM) -> Val {
+        vm.intern_symbol(self.s.to_string())
+    }
+
+    /// How many characters does this string contain? Note that, as with all indexing operations
+    /// on strings, "character" means "Unicode code point".
+    pub fn length(&self) -> usize {
+        if self.is_ascii {
+            self.s.len()
+        } else {
+            self.s.chars().count()
+        }
+    }
+
+    /// Return the byte offset in `s` of the 0-based code point index `i`, which must be no
+    /// greater than `self.length()`.
+    fn byte_offset(&self, i: usize) -> usize {
+        if self.is_ascii {
+            i
+        } else {
+            self.s
+                .char_indices()
+                .nth(i)
+                .map(|(off, _)| off)
+                .unwrap_or_else(|| self.s.len())
+        }
     }
 
-    pub fn to_symbol(&self, vm: &VM) -> Result<Val, Box<VMError>> {
-        Ok(String_::new(vm, self.s.to_string(), false))
+    /// Return the character at SOM index `idx` as a new string.
+    pub fn at(&self, vm: &VM, idx: Val) -> Result<Val, Box<VMError>> {
+        let i = idx.as_index(vm, self.length())?;
+        let c = self.s[self.byte_offset(i)..].chars().next().unwrap();
+        Ok(String_::new(vm, c.to_string()))
+    }
+
+    /// Return the characters from SOM index `start` to `end` (inclusive) as a new string. If
+    /// `end` is one less than `start`, the empty string is returned.
+    pub fn substring(&self, vm: &VM, start: Val, end: Val) -> Result<Val, Box<VMError>> {
+        let len = self.length();
+        let start_i = start.as_index(vm, len + 1)?;
+        // `start_i` is 0-based, so if `end` is equal to it, the empty string has been requested.
+        let end_i = if end.as_isize(vm) == Some(start_i as isize) {
+            start_i
+        } else {
+            end.as_index(vm, len)? + 1
+        };
+        if end_i < start_i {
+            return Err(Box::new(VMError::IndexError {
+                tried: end_i as isize,
+                max: len,
+            }));
+        }
+        let start_off = self.byte_offset(start_i);
+        let end_off = self.byte_offset(end_i);
+        Ok(String_::new(vm, self.s[start_off..end_off].to_owned()))
+    }
+
+    /// Return the SOM index of the first occurrence of the string `other` in this string, or 0
+    /// if it does not occur.
+    pub fn index_of(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        let other_str = String_::from_val(vm, &other)?;
+        match self.s.find(&other_str.s) {
+            Some(off) if self.is_ascii => Val::from_usize(vm, off + 1),
+            Some(off) => Val::from_usize(vm, self.s[..off].chars().count() + 1),
+            None => Val::from_usize(vm, 0),
+        }
+    }
+
+    /// Does this string begin with the string `other`?
+    pub fn begins_with(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        let other_str = String_::from_val(vm, &other)?;
+        Ok(Val::from_bool(vm, self.s.starts_with(&other_str.s)))
+    }
+
+    /// Does this string end with the string `other`?
+    pub fn ends_with(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        let other_str = String_::from_val(vm, &other)?;
+        Ok(Val::from_bool(vm, self.s.ends_with(&other_str.s)))
+    }
+
+    /// Return a new string with every character of this string converted to upper case. Note that
+    /// this uses Unicode's full case mappings, so the new string may have a different length to
+    /// this string.
+    pub fn to_uppercase(&self, vm: &VM) -> Val {
+        String_::new(vm, self.s.to_uppercase())
+    }
+
+    /// Return a new string with every character of this string converted to lower case: see
+    /// `to_uppercase`.
+    pub fn to_lowercase(&self, vm: &VM) -> Val {
+        String_::new(vm, self.s.to_lowercase())
+    }
+
+    /// Is this string non-empty and does every character satisfy `f`?
+    pub fn all_chars<F>(&self, vm: &VM, f: F) -> Val
+    where
+        F: Fn(char) -> bool,
+    {
+        Val::from_bool(vm, !self.s.is_empty() && self.s.chars().all(f))
+    }
+
+    /// Split this string on every occurrence of the string `sep`, returning an `Array` of
+    /// strings. Splitting on the empty string returns an `Array` containing only this string.
+    pub fn split(&self, vm: &VM, sep: Val) -> Result<Val, Box<VMError>> {
+        let sep_str = String_::from_val(vm, &sep)?;
+        if sep_str.s.is_empty() {
+            return Ok(Array::from_vec(vm, vec![String_::new(vm, self.s.clone())]));
+        }
+        Ok(Array::from_vec(
+            vm,
+            self.s
+                .split(&sep_str.s)
+                .map(|x| String_::new(vm, x.to_owned()))
+                .collect(),
+        ))
+    }
+
+    /// Parse this string (ignoring leading and trailing whitespace) as a SOM integer, returning
+    /// `nil` if it is not a valid integer.
+    pub fn to_integer(&self, vm: &VM) -> Result<Val, Box<VMError>> {
+        match BigInt::from_str(self.s.trim()) {
+            Ok(i) => ArbInt::new(vm, i),
+            Err(_) => Ok(vm.nil.clone()),
+        }
     }
 }
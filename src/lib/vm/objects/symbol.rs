@@ -0,0 +1,69 @@
+#![allow(clippy::new_ret_no_self)]
+
+use abgc_derive::GcLayout;
+
+use crate::vm::{
+    core::{VMError, VM},
+    objects::{Obj, ObjType, StaticObjType, String_},
+    val::{NotUnboxable, Val},
+};
+
+/// A SOM symbol. Symbols are always interned by the VM (see `VM::intern_symbol`), so two symbols
+/// with the same characters are the same object, and reference equality is a pointer comparison.
+#[derive(Debug, GcLayout)]
+pub struct Symbol {
+    /// The `String_` holding this symbol's characters.
+    string: Val,
+    /// The hash of `string`, cached since `Obj::hashcode` does not have access to the VM.
+    hash: u64,
+}
+
+impl Obj for Symbol {
+    fn dyn_objtype(&self) -> ObjType {
+        ObjType::Symbol
+    }
+
+    fn get_class(&self, vm: &VM) -> Val {
+        vm.sym_cls.clone()
+    }
+
+    fn hashcode(&self) -> u64 {
+        // Symbols hash the same as strings with the same characters, since the two compare equal.
+        self.hash
+    }
+
+    fn to_strval(&self, _: &VM) -> Result<Val, Box<VMError>> {
+        Ok(self.string.clone())
+    }
+
+    fn equals(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        self.string.equals(vm, other)
+    }
+
+    fn less_than(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        self.string.less_than(vm, other)
+    }
+}
+
+impl NotUnboxable for Symbol {}
+
+impl StaticObjType for Symbol {
+    fn static_objtype() -> ObjType {
+        ObjType::Symbol
+    }
+}
+
+impl Symbol {
+    /// Create a new symbol. This must only be called by the VM's symbol table: all other code
+    /// should use `VM::intern_symbol`.
+    pub(crate) fn new(vm: &VM, s: String) -> Val {
+        let string = String_::new(vm, s);
+        let hash = string.tobj(vm).unwrap().hashcode();
+        Val::from_obj(vm, Symbol { string, hash })
+    }
+
+    /// Return the `String_` holding this symbol's characters.
+    pub fn string(&self, vm: &VM) -> &String_ {
+        self.string.downcast(vm).unwrap()
+    }
+}
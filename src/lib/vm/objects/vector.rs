@@ -0,0 +1,110 @@
+#![allow(clippy::new_ret_no_self)]
+
+use std::{
+    cell::UnsafeCell,
+    collections::VecDeque,
+    mem::{size_of, size_of_val},
+};
+
+use abgc_derive::GcLayout;
+
+use crate::vm::{
+    core::{VMError, VM},
+    objects::{Array, Obj, ObjType, StaticObjType},
+    val::{NotUnboxable, Val},
+};
+
+/// A growable sequence of SOM values, which can be efficiently added to and removed from at both
+/// ends.
+#[derive(Debug, GcLayout)]
+pub struct Vector {
+    /// The class of this vector: either `Vector` or a subclass of it (e.g. `OrderedCollection`).
+    class: Val,
+    store: UnsafeCell<VecDeque<Val>>,
+}
+
+impl Obj for Vector {
+    fn dyn_objtype(&self) -> ObjType {
+        ObjType::Vector
+    }
+
+    fn get_class(&self, _: &VM) -> Val {
+        self.class.clone()
+    }
+
+    fn object_size(&self) -> usize {
+        size_of_val(self) + self.size() * size_of::<Val>()
+    }
+}
+
+impl NotUnboxable for Vector {}
+
+impl StaticObjType for Vector {
+    fn static_objtype() -> ObjType {
+        ObjType::Vector
+    }
+}
+
+impl Vector {
+    /// Create a new, empty, vector which is an instance of `class`.
+    pub fn new(vm: &VM, class: Val) -> Val {
+        Val::from_obj(
+            vm,
+            Vector {
+                class,
+                store: UnsafeCell::new(VecDeque::new()),
+            },
+        )
+    }
+
+    /// How many elements does this vector have?
+    pub fn size(&self) -> usize {
+        unsafe { &*self.store.get() }.len()
+    }
+
+    /// Return the element at SOM index `idx`.
+    pub fn at(&self, vm: &VM, idx: Val) -> Result<Val, Box<VMError>> {
+        let store = unsafe { &*self.store.get() };
+        Ok(store[idx.as_index(vm, store.len())?].clone())
+    }
+
+    /// Set the element at SOM index `idx` to `val`.
+    pub fn at_put(&self, vm: &VM, idx: Val, val: Val) -> Result<(), Box<VMError>> {
+        let store = unsafe { &mut *self.store.get() };
+        let i = idx.as_index(vm, store.len())?;
+        store[i] = val;
+        Ok(())
+    }
+
+    /// Add `val` to the end of this vector.
+    pub fn append(&self, val: Val) {
+        unsafe { &mut *self.store.get() }.push_back(val);
+    }
+
+    /// Remove and return the first element of this vector.
+    pub fn remove_first(&self) -> Result<Val, Box<VMError>> {
+        unsafe { &mut *self.store.get() }
+            .pop_front()
+            .ok_or_else(|| Box::new(VMError::EmptyCollection))
+    }
+
+    /// Remove and return the last element of this vector.
+    pub fn remove_last(&self) -> Result<Val, Box<VMError>> {
+        unsafe { &mut *self.store.get() }
+            .pop_back()
+            .ok_or_else(|| Box::new(VMError::EmptyCollection))
+    }
+
+    /// Return a new `Array` containing this vector's elements.
+    pub fn to_array(&self, vm: &VM) -> Val {
+        Array::from_vec(vm, unsafe { &*self.store.get() }.iter().cloned().collect())
+    }
+
+    /// Sort this vector in place using the comparator block `cmp`: see `Array::sort`.
+    pub fn sort(&self, vm: &VM, cmp: Val) -> Result<(), Box<VMError>> {
+        let mut vals = unsafe { &*self.store.get() }.iter().cloned().collect();
+        Array::sort_vals(vm, &mut vals, &cmp)?;
+        *unsafe { &mut *self.store.get() } = vals.into();
+        Ok(())
+    }
+}
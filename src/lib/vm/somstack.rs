@@ -49,6 +49,47 @@ impl SOMStack {
         v2
     }
 
+    /// Returns the value `n` elements below the top-most value of the stack (i.e. `peek_n(0)` is
+    /// equivalent to `peek()`) without removing it. If the stack has `n` or fewer elements,
+    /// calling this function will lead to undefined behaviour.
+    pub fn peek_n(&self, n: usize) -> Val {
+        debug_assert!(n < self.len());
+        let v = unsafe { ptr::read(self.storage.add(self.len - 1 - n)) };
+        let v2 = v.clone();
+        forget(v);
+        v2
+    }
+
+    /// Returns the value at index `i` (where index 0 is the bottom of the stack) without removing
+    /// it. If the stack has `i` or fewer elements, calling this function will lead to undefined
+    /// behaviour.
+    pub fn get(&self, i: usize) -> Val {
+        debug_assert!(i < self.len());
+        let v = unsafe { ptr::read(self.storage.add(i)) };
+        let v2 = v.clone();
+        forget(v);
+        v2
+    }
+
+    /// Replaces the value at index `i` (where index 0 is the bottom of the stack) with `v`. If the
+    /// stack has `i` or fewer elements, calling this function will lead to undefined behaviour.
+    pub fn set(&mut self, i: usize, v: Val) {
+        debug_assert!(i < self.len());
+        unsafe { *self.storage.add(i) = v };
+    }
+
+    /// Inserts `v` at index `i`, shifting all values at or above `i` up by one. You must
+    /// previously have checked (using [`SOMStack::remaining_capacity`]) that there is room for
+    /// this value: if there is not, undefined behaviour will occur.
+    pub fn insert(&mut self, i: usize, v: Val) {
+        debug_assert!(i <= self.len() && self.remaining_capacity() > 0);
+        unsafe {
+            ptr::copy(self.storage.add(i), self.storage.add(i + 1), self.len - i);
+            ptr::write(self.storage.add(i), v);
+        }
+        self.len += 1;
+    }
+
     /// Pops the top-most value of the stack and returns it. If the stack is empty, calling
     /// this function will lead to undefined behaviour.
     pub fn pop(&mut self) -> Val {
@@ -77,6 +118,13 @@ impl SOMStack {
         self.len += 1;
     }
 
+    /// Removes the top-most `n` values of the stack. If the stack has fewer than `n` elements,
+    /// calling this function will lead to undefined behaviour.
+    pub fn truncate_n(&mut self, n: usize) {
+        debug_assert!(n <= self.len());
+        self.truncate(self.len - n);
+    }
+
     /// Shortens the stack, keeping the first len elements and dropping the rest.
     pub fn truncate(&mut self, len: usize) {
         debug_assert!(len <= self.len());
@@ -3,7 +3,10 @@
 #![allow(clippy::new_ret_no_self)]
 
 use std::{
+    cmp::Ordering,
+    collections::hash_map::DefaultHasher,
     convert::TryFrom,
+    hash::{Hash, Hasher},
     mem::{size_of, transmute},
     ops::Deref,
     ptr::NonNull,
@@ -16,7 +19,10 @@ use num_traits::{FromPrimitive, ToPrimitive, Zero};
 
 use super::{
     core::{VMError, VM},
-    objects::{ArbInt, Double, Int, Obj, ObjType, StaticObjType, String_, ThinObj},
+    objects::{
+        ArbInt, Class, Double, Fraction, FractionOp, Inst, Int, Obj, ObjType, StaticObjType,
+        String_, ThinObj,
+    },
 };
 
 // We use a fairly standard pointer tagging model where the low `TAG_BITSIZE` bits of a machine
@@ -255,12 +261,38 @@ impl Val {
         }
     }
 
+    /// Convert this `Val`, which must be a SOM integer, into a 0-based index into a collection of
+    /// `len` elements. Note that SOM indexes start from 1, so the SOM index 1 is converted to 0.
+    pub fn as_index(&self, vm: &VM, len: usize) -> Result<usize, Box<VMError>> {
+        match self.as_isize(vm) {
+            Some(i) if i > 0 && (i as usize) <= len => Ok((i as usize) - 1),
+            Some(i) => Err(Box::new(VMError::IndexError { tried: i, max: len })),
+            None => {
+                if self.try_downcast::<ArbInt>(vm).is_some() {
+                    Err(Box::new(VMError::CantRepresentAsIsize))
+                } else {
+                    Err(Box::new(VMError::TypeError {
+                        expected: ObjType::Int,
+                        got: self.dyn_objtype(vm),
+                    }))
+                }
+            }
+        }
+    }
+
     /// Is this `Val` bit equal to `other`? This is a very strong property, generally used as a
     /// fast proxy for "if both `Val`s are `GCBox`s then do they point to the same thing?" since,
     /// in such cases, at least one of the sides has been pre-guaranteed to be a `GCBox`.
     pub fn bit_eq(&self, other: &Val) -> bool {
         self.val == other.val
     }
+
+    /// Return a `usize` which identifies this `Val`: two `Val`s have the same identity if, and
+    /// only if, they are `bit_eq`. Note that the identity of a boxed object may be reused after
+    /// the object has been collected.
+    pub fn identity(&self) -> usize {
+        self.val
+    }
 }
 
 // Implement each function from the `Obj` type so that we can efficiently deal with tagged values.
@@ -283,18 +315,117 @@ impl Val {
         }
     }
 
+    /// Return this `Val`'s hashcode as a SOM integer.
+    pub fn hashcode(&self, vm: &VM) -> Result<Val, Box<VMError>> {
+        let h = match self.valkind() {
+            ValKind::INT => {
+                let mut hasher = DefaultHasher::new();
+                self.as_isize(vm).unwrap().hash(&mut hasher);
+                hasher.finish()
+            }
+            ValKind::GCBOX => self.tobj(vm)?.hashcode(),
+            ValKind::ILLEGAL => unreachable!(),
+        };
+        // Dropping the top bits of the hash guarantees that it can be stored as a tagged integer.
+        Val::from_usize(vm, (h >> (TAG_BITSIZE + 1)) as usize)
+    }
+
+    /// How many bytes does this `Val` occupy?
+    pub fn object_size(&self, vm: &VM) -> usize {
+        match self.valkind() {
+            ValKind::INT => size_of::<Val>(),
+            ValKind::GCBOX => self.tobj(vm).unwrap().object_size(),
+            ValKind::ILLEGAL => unreachable!(),
+        }
+    }
+
+    /// Return the instance variable at the SOM index `idx`. Only instances of user classes have
+    /// instance variables: all other objects are treated as having none.
+    pub fn inst_var_at(&self, vm: &VM, idx: Val) -> Result<Val, Box<VMError>> {
+        match self.try_downcast::<Inst>(vm) {
+            Some(inst) => Ok(inst.inst_var_lookup(idx.as_index(vm, inst.num_inst_vars())?)),
+            // Since there are no instance variables, `as_index` is guaranteed to fail.
+            None => Err(idx.as_index(vm, 0).unwrap_err()),
+        }
+    }
+
+    /// Set the instance variable at the SOM index `idx` to `val`, returning `val`.
+    pub fn inst_var_at_put(&self, vm: &VM, idx: Val, val: Val) -> Result<Val, Box<VMError>> {
+        match self.try_downcast::<Inst>(vm) {
+            Some(inst) => {
+                inst.inst_var_set(idx.as_index(vm, inst.num_inst_vars())?, val.clone());
+                Ok(val)
+            }
+            // Since there are no instance variables, `as_index` is guaranteed to fail.
+            None => Err(idx.as_index(vm, 0).unwrap_err()),
+        }
+    }
+
+    /// Return the instance variable `name`.
+    pub fn inst_var_named(&self, vm: &VM, name: &str) -> Result<Val, Box<VMError>> {
+        if let Some(inst) = self.try_downcast::<Inst>(vm) {
+            let cls_val = self.get_class(vm);
+            let cls: &Class = cls_val.downcast(vm)?;
+            if let Some(i) = cls.inst_vars.iter().position(|x| x == name) {
+                return Ok(inst.inst_var_lookup(i));
+            }
+        }
+        Err(Box::new(VMError::UnknownInstVar(name.to_owned())))
+    }
+
     pub fn to_strval(&self, vm: &VM) -> Result<Val, Box<VMError>> {
         match self.valkind() {
-            ValKind::INT => Ok(String_::new(
-                vm,
-                self.as_isize(vm).unwrap().to_string(),
-                true,
-            )),
+            ValKind::INT => Ok(String_::new(vm, self.as_isize(vm).unwrap().to_string())),
             ValKind::GCBOX => self.tobj(vm).unwrap().to_strval(vm),
             ValKind::ILLEGAL => unreachable!(),
         }
     }
 
+    /// Convert this `Val`, which must be a SOM integer, into a `Val` that represents a SOM string
+    /// of the integer in base `radix`.
+    pub fn to_strval_radix(&self, vm: &VM, radix: Val) -> Result<Val, Box<VMError>> {
+        let radix = match radix.as_isize(vm) {
+            Some(r) if (2..=36).contains(&r) => r as u32,
+            Some(_) => return Err(Box::new(VMError::DomainError)),
+            None => {
+                return Err(Box::new(VMError::NotANumber {
+                    got: radix.dyn_objtype(vm),
+                }))
+            }
+        };
+        let s = if let Some(i) = self.as_isize(vm) {
+            BigInt::from_isize(i).unwrap().to_str_radix(radix)
+        } else if let Some(i) = self.try_downcast::<ArbInt>(vm) {
+            i.bigint().to_str_radix(radix)
+        } else {
+            return Err(Box::new(VMError::ReceiverNotANumber {
+                got: self.dyn_objtype(vm),
+            }));
+        };
+        Ok(String_::new(vm, s))
+    }
+
+    /// Convert this `Val`, which must be a SOM number, into a `Val` that represents a SOM
+    /// `Double`.
+    pub fn to_doubleval(&self, vm: &VM) -> Result<Val, Box<VMError>> {
+        if let Some(i) = self.as_isize(vm) {
+            Ok(Double::new(vm, i as f64))
+        } else if let Some(i) = self.try_downcast::<ArbInt>(vm) {
+            match i.bigint().to_f64() {
+                Some(d) => Ok(Double::new(vm, d)),
+                None => Err(Box::new(VMError::CantRepresentAsDouble)),
+            }
+        } else if self.try_downcast::<Double>(vm).is_some() {
+            Ok(self.clone())
+        } else if let Some(f) = self.try_downcast::<Fraction>(vm) {
+            Ok(Double::new(vm, f.to_f64()?))
+        } else {
+            Err(Box::new(VMError::ReceiverNotANumber {
+                got: self.dyn_objtype(vm),
+            }))
+        }
+    }
+
     /// Produce a new `Val` which adds `other` to this.
     pub fn add(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
         debug_assert_eq!(ValKind::INT as usize, 0);
@@ -303,6 +434,9 @@ impl Val {
                 return Ok(Val { val });
             }
         }
+        if other.try_downcast::<Fraction>(vm).is_some() {
+            return Fraction::arith(vm, self, &other, FractionOp::Add);
+        }
         self.tobj(vm).unwrap().add(vm, other)
     }
 
@@ -334,11 +468,17 @@ impl Val {
                 return Err(Box::new(VMError::DivisionByZero));
             }
         }
+        if other.try_downcast::<Fraction>(vm).is_some() {
+            return Fraction::arith(vm, self, &other, FractionOp::Div);
+        }
         self.tobj(vm).unwrap().div(vm, other)
     }
 
     /// Produce a new `Val` which perfoms a Double divide on `other` with this.
     pub fn double_div(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        if other.try_downcast::<Fraction>(vm).is_some() {
+            return Fraction::arith(vm, self, &other, FractionOp::DoubleDiv);
+        }
         if let Some(lhs) = self.as_isize(vm) {
             if let Some(rhs) = other.as_isize(vm) {
                 if rhs == 0 {
@@ -393,9 +533,48 @@ impl Val {
                 return Ok(Val { val });
             }
         }
+        if other.try_downcast::<Fraction>(vm).is_some() {
+            return Fraction::arith(vm, self, &other, FractionOp::Mul);
+        }
         self.tobj(vm).unwrap().mul(vm, other)
     }
 
+    /// Produce a new `Val` which performs a bitwise or operation with `other` and this.
+    pub fn or(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        if let Some(lhs) = self.as_isize(vm) {
+            if let Some(rhs) = other.as_isize(vm) {
+                return Val::from_isize(vm, lhs | rhs);
+            } else if let Some(rhs) = other.try_downcast::<ArbInt>(vm) {
+                return ArbInt::new(vm, BigInt::from_isize(lhs).unwrap() | rhs.bigint());
+            }
+            return Err(Box::new(VMError::TypeError {
+                expected: self.dyn_objtype(vm),
+                got: other.dyn_objtype(vm),
+            }));
+        }
+        self.tobj(vm).unwrap().or(vm, other)
+    }
+
+    /// Produce a new `Val` which is the remainder of dividing this by `other`. The result has the
+    /// same sign as this.
+    pub fn rem(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        if let Some(lhs) = self.as_isize(vm) {
+            if let Some(rhs) = other.as_isize(vm) {
+                if rhs == 0 {
+                    return Err(Box::new(VMError::DivisionByZero));
+                }
+                // The only case which can overflow is `isize::min_value() rem: -1`, which is 0.
+                return Val::from_isize(vm, lhs.checked_rem(rhs).unwrap_or(0));
+            } else if let Some(rhs) = other.try_downcast::<ArbInt>(vm) {
+                return ArbInt::new(vm, BigInt::from_isize(lhs).unwrap() % rhs.bigint());
+            }
+            return Err(Box::new(VMError::NotANumber {
+                got: other.dyn_objtype(vm),
+            }));
+        }
+        self.tobj(vm).unwrap().rem(vm, other)
+    }
+
     /// Produce a new `Val` which shifts `self` `other` bits to the left.
     pub fn shl(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
         if let Some(lhs) = self.as_isize(vm) {
@@ -431,6 +610,31 @@ impl Val {
         self.tobj(vm).unwrap().shl(vm, other)
     }
 
+    /// Produce a new `Val` which arithmetically shifts `self` `other` bits to the right (i.e. the
+    /// result is rounded towards negative infinity).
+    pub fn shr(&self, vm: &VM, other: Val) -> Result<Val, Box<VMError>> {
+        if let Some(lhs) = self.as_isize(vm) {
+            // If every bit is shifted out, the result depends only on the sign of `lhs`.
+            let all_out = if lhs < 0 { -1 } else { 0 };
+            if let Some(rhs) = other.as_isize(vm) {
+                if rhs < 0 {
+                    return Err(Box::new(VMError::NegativeShift));
+                }
+                let rhs_i = u32::try_from(rhs).unwrap_or(u32::max_value());
+                return Val::from_isize(vm, lhs.checked_shr(rhs_i).unwrap_or(all_out));
+            } else if let Some(rhs) = other.try_downcast::<ArbInt>(vm) {
+                if *rhs.bigint() < Zero::zero() {
+                    return Err(Box::new(VMError::NegativeShift));
+                }
+                return Val::from_isize(vm, all_out);
+            }
+            return Err(Box::new(VMError::NotANumber {
+                got: other.dyn_objtype(vm),
+            }));
+        }
+        self.tobj(vm).unwrap().shr(vm, other)
+    }
+
     /// Produces a new `Val` which is the square root of this.
     pub fn sqrt(&self, vm: &VM) -> Result<Val, Box<VMError>> {
         if let Some(lhs) = self.as_isize(vm) {
@@ -456,6 +660,9 @@ impl Val {
                 return Ok(Val { val });
             }
         }
+        if other.try_downcast::<Fraction>(vm).is_some() {
+            return Fraction::arith(vm, self, &other, FractionOp::Sub);
+        }
         self.tobj(vm).unwrap().sub(vm, other)
     }
 
@@ -496,9 +703,19 @@ macro_rules! binop_all {
                     } else if let Some(rhs) = other.try_downcast::<ArbInt>(vm) {
                         Ok(Val::from_bool(vm,
                             &BigInt::from_isize(lhs).unwrap() $op rhs.bigint()))
+                    } else if other.try_downcast::<Fraction>(vm).is_some() {
+                        Ok(match Fraction::compare(vm, self, &other) {
+                            Ok(Some(o)) => Val::from_bool(vm, o $op Ordering::Equal),
+                            _ => vm.$tf.clone(),
+                        })
                     } else {
                         Ok(vm.$tf.clone())
                     }
+                } else if other.try_downcast::<Fraction>(vm).is_some() {
+                    Ok(match Fraction::compare(vm, self, &other) {
+                        Ok(Some(o)) => Val::from_bool(vm, o $op Ordering::Equal),
+                        _ => vm.$tf.clone(),
+                    })
                 } else {
                     self.tobj(vm).unwrap().$name(vm, other)
                 }
@@ -517,11 +734,17 @@ macro_rules! binop_typeerror {
                     } else if let Some(rhs) = other.try_downcast::<ArbInt>(vm) {
                         Ok(Val::from_bool(vm,
                             &BigInt::from_isize(lhs).unwrap() $op rhs.bigint()))
+                    } else if other.try_downcast::<Fraction>(vm).is_some() {
+                        let o = Fraction::compare(vm, self, &other)?;
+                        Ok(Val::from_bool(vm, o.map_or(false, |o| o $op Ordering::Equal)))
                     } else {
                         Err(Box::new(VMError::NotANumber {
                           got: other.dyn_objtype(vm),
                         }))
                     }
+                } else if other.try_downcast::<Fraction>(vm).is_some() {
+                    let o = Fraction::compare(vm, self, &other)?;
+                    Ok(Val::from_bool(vm, o.map_or(false, |o| o $op Ordering::Equal)))
                 } else {
                     self.tobj(vm).unwrap().$name(vm, other)
                 }
@@ -656,7 +879,7 @@ mod tests {
         let vm = VM::new_no_bootstrap();
 
         let v = {
-            let v = String_::new(&vm, "s".to_owned(), true);
+            let v = String_::new(&vm, "s".to_owned());
             let v_tobj = v.tobj(&vm).unwrap();
             let v_int: &dyn Obj = v_tobj.deref().deref();
             let v_recovered = Val::recover(v_int);
@@ -671,7 +894,7 @@ mod tests {
     #[test]
     fn test_cast() {
         let vm = VM::new_no_bootstrap();
-        let v = String_::new(&vm, "s".to_owned(), true);
+        let v = String_::new(&vm, "s".to_owned());
         assert!(v.downcast::<String_>(&vm).is_ok());
         assert_eq!(
             *v.downcast::<Class>(&vm).unwrap_err(),
@@ -685,7 +908,7 @@ mod tests {
     #[test]
     fn test_downcast() {
         let vm = VM::new_no_bootstrap();
-        let v = String_::new(&vm, "s".to_owned(), true);
+        let v = String_::new(&vm, "s".to_owned());
         assert!(v.downcast::<String_>(&vm).is_ok());
         assert!(v.downcast::<Class>(&vm).is_err());
         assert!(v.try_downcast::<String_>(&vm).is_some());
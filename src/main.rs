@@ -2,14 +2,14 @@
 
 use std::{
     env,
-    io::{stderr, Write},
+    io::{stderr, stdout, Write},
     path::Path,
     process,
 };
 
 use getopts::Options;
 
-use yksom::vm::{objects::Inst, VMError, VM};
+use yksom::vm::{objects::Inst, VMError, VMOptions, VM};
 
 fn usage(prog: &str) -> ! {
     let path = Path::new(prog);
@@ -17,7 +17,12 @@ fn usage(prog: &str) -> ! {
         .file_name()
         .map(|x| x.to_str().unwrap_or("yksom"))
         .unwrap_or("yksom");
-    writeln!(&mut stderr(), "Usage: {} [-h] --cp <path> <file.som>", leaf).ok();
+    writeln!(
+        &mut stderr(),
+        "Usage: {} [-h] [--fractions] [--ic-stats] --cp <path> <file.som>",
+        leaf
+    )
+    .ok();
     process::exit(1)
 }
 
@@ -26,6 +31,12 @@ fn main() {
     let prog = &args[0];
     let matches = Options::new()
         .optmulti("", "cp", "Path to System classes", "<path>")
+        .optflag("", "fractions", "Integer division produces exact fractions")
+        .optflag(
+            "",
+            "ic-stats",
+            "Print inline cache statistics to stderr on exit",
+        )
         .optflag("h", "help", "")
         .parse(&args[1..])
         .unwrap_or_else(|_| usage(prog));
@@ -33,11 +44,22 @@ fn main() {
         usage(prog);
     }
 
-    let vm = VM::new(matches.opt_strs("cp"));
+    let opts = VMOptions {
+        fractions: matches.opt_present("fractions"),
+    };
+    let vm = VM::new(matches.opt_strs("cp"), opts);
     let cls = vm.compile(&Path::new(&matches.free[0]).canonicalize().unwrap(), true);
     let app = Inst::new(&vm, cls);
-    match vm.send(app, "run", vec![]) {
-        Ok(_) | Err(box VMError::Exit) => (),
+    let r = vm.send(app, "run", vec![]);
+    if matches.opt_present("ic-stats") {
+        eprint!("{}", vm.inline_cache_stats());
+    }
+    match r {
+        Ok(_) => (),
+        Err(box VMError::Exit(code)) => {
+            stdout().flush().ok();
+            process::exit(code);
+        }
         Err(e) => {
             eprintln!("{:?}", e);
             process::exit(1);